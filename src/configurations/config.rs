@@ -1,10 +1,14 @@
 use rocket::{config::SecretKey, figment::Figment};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use tokio::sync::RwLock;
 
 use crate::ldap::ldap::LdapAttributes;
+use crate::model::entity::{approver::ApproverGroup, client::ClientLimits, portal_form::PortalForm};
+use crate::unifi::unifi::UnifiPlatform;
 
 // Types
 pub type ConfigApp = RwLock<ConfigApplication>;
@@ -30,9 +34,12 @@ pub struct DatabaseConfig {
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct UnifiConfig {
+    pub name: String,
+    pub sites: Option<Vec<String>>,
     pub url: String,
     pub username: String,
     pub password: String,
+    pub platform: Option<UnifiPlatform>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -40,7 +47,131 @@ pub struct ClientsConfig {
     pub free_request: bool,
     pub time_connection: usize,
     pub expiration_time: Option<usize>,
+    /// Regex per field, superseded by `forms`
     pub info: Option<ClientInfo>,
+    pub forms: Option<Vec<PortalForm>>,
+    pub events: Option<bool>,
+    pub limits: Option<ClientLimits>,
+    pub groups_limits: Option<HashMap<ApproverGroup, ClientLimits>>,
+    /// Removes the access of guests authorized on UniFi without an approved record
+    pub unauthorize_unknown: Option<bool>,
+    pub user_groups: Option<UserGroupsConfig>,
+    /// Minutes a guest has to finish the portal after the UniFi redirect
+    pub portal_session_minutes: Option<i64>,
+    /// Requires the IP of the portal request to be the one the controller reports for the device,
+    /// on by default. Behind a reverse proxy the client address must be forwarded in `X-Real-IP`
+    pub portal_check_ip: Option<bool>,
+    pub redirect: Option<RedirectConfig>,
+    pub email_verification: Option<EmailVerificationConfig>,
+    /// Takes the place of `email_verification` when both are set
+    pub sms_verification: Option<SmsVerificationConfig>,
+}
+
+/// One-time code mailed to self-registered guests before their request is saved
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EmailVerificationConfig {
+    /// Read once at launch, a change needs a restart
+    pub smtp: SmtpConfig,
+    pub code_size: usize,
+    /// Validity of the code, also the time the guest stays authorized to read the mail
+    pub code_minutes: u16,
+    pub subject: String,
+    /// `{code}` and `{minutes}` are replaced
+    pub body: String,
+    /// Codes sent to one address within the window
+    pub max_per_address: usize,
+    /// Codes sent to one device within the window
+    pub max_per_mac: usize,
+    pub window_minutes: i64,
+}
+
+/// One-time code texted to self-registered guests before their request is saved
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SmsVerificationConfig {
+    pub gateway: SmsGatewayConfig,
+    pub code_size: usize,
+    /// Validity of the code
+    pub code_minutes: u16,
+    /// `{code}` and `{minutes}` are replaced
+    pub message: String,
+    /// Codes sent to one phone number within the window
+    pub max_per_phone: usize,
+    /// Codes sent to one device within the window
+    pub max_per_mac: usize,
+    pub window_minutes: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum SmsGatewayConfig {
+    /// Posts every message as JSON to an HTTP endpoint of the provider
+    Webhook(SmsWebhookConfig),
+    /// Only prints the messages, for tests and development
+    Log,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SmsWebhookConfig {
+    pub url: String,
+    /// Sent with every request, e.g. the `Authorization` of the provider
+    pub headers: Option<HashMap<String, String>>,
+    /// Body keys holding the phone number and the text
+    pub to_field: String,
+    pub message_field: String,
+    /// Fixed values added to the body, e.g. the sender id
+    pub extra: Option<Map<String, Value>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub security: SmtpSecurity,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum SmtpSecurity {
+    None,
+    StartTls,
+    Tls,
+}
+
+/// Page opened by the guest after the approval
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RedirectConfig {
+    /// Checked in order, the first one matching the site and SSID wins
+    pub success_pages: Vec<SuccessPage>,
+    /// Hosts the original URL may point to, `*.` matches any subdomain. Empty never sends the guest back
+    pub allowed_hosts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SuccessPage {
+    pub site: String,
+    /// Any SSID of the site when missing
+    pub ssid: Option<String>,
+    pub url: String,
+}
+
+/// UniFi user group (by name) given to the guests, the first mapping that matches wins
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct UserGroupsConfig {
+    /// By username of the `User` account the guest logged in with
+    pub users: Option<HashMap<String, String>>,
+    /// By group of the approver that released the guest
+    pub approvers: Option<HashMap<ApproverGroup, String>>,
+    /// By value of a form field, checked in the order they are written
+    pub fields: Option<Vec<FieldGroup>>,
+    pub default: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FieldGroup {
+    pub field: String,
+    pub value: String,
+    pub group: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -57,6 +188,8 @@ pub struct ApproversConfig {
     pub validity_days_code: usize,
     pub just_numbers: bool,
     pub encrypted_code: bool,
+    /// Longest extension an approver can grant at once, unlimited when missing
+    pub max_extension_minutes: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -99,30 +232,49 @@ pub struct GLPIConfig {
     pub template_solution_id: usize
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WlansConfig {
+    /// Guest SSIDs whose passphrase is rotated
+    pub rotate: Vec<WlanRotation>,
+    pub rotation_hours: u64,
+    pub passphrase_size: usize,
+    pub just_numbers: bool,
+    /// Token the reception kiosk sends in the `X-Kiosk-Token` header, the kiosk endpoint is off without it
+    pub kiosk_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WlanRotation {
+    pub site: String,
+    pub ssid: String,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ConfigApplication {
     pub server: ServerConfig,
-    pub unifi: UnifiConfig,
+    pub unifi: Vec<UnifiConfig>,
     pub database: DatabaseConfig,
     pub clients: ClientsConfig,
     pub approvers: ApproversConfig,
     pub admins: AdminsConfig,
     pub users: UsersConfig,
     pub ldap: Option<LdapConfig>,
-    pub glpi: Option<GLPIConfig>
+    pub glpi: Option<GLPIConfig>,
+    pub wlans: Option<WlansConfig>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ConfigUpdate {
     pub server: Option<ServerConfig>,
-    pub unifi: Option<UnifiConfig>,
+    pub unifi: Option<Vec<UnifiConfig>>,
     pub database: Option<DatabaseConfig>,
     pub clients: Option<ClientsConfig>,
     pub approvers: Option<ApproversConfig>,
     pub admins: Option<AdminsConfig>,
     pub users: Option<UsersConfig>,
     pub ldap: Option<LdapConfig>,
-    pub glpi: Option<GLPIConfig>
+    pub glpi: Option<GLPIConfig>,
+    pub wlans: Option<WlansConfig>,
 }
 
 // Impls
@@ -168,6 +320,110 @@ impl ConfigApplication {
     }
 }
 
+impl ClientsConfig {
+    /// Form of the site/SSID, built from `info` when no form matches
+    pub fn form_for(&self, site: &str, ssid: Option<&str>) -> PortalForm {
+        let form = self
+            .forms
+            .as_ref()
+            .and_then(|forms| forms.iter().find(|f| f.matches(site, ssid)));
+
+        match (form, &self.info) {
+            (Some(form), _) => form.clone(),
+            (None, Some(info)) => PortalForm::new_with_info(info),
+            (None, None) => PortalForm::default(),
+        }
+    }
+
+    pub fn user_group_for(
+        &self,
+        user: Option<&str>,
+        approver: Option<&ApproverGroup>,
+        fields: &HashMap<String, String>,
+    ) -> Option<String> {
+        self.user_groups.as_ref()?.group_for(user, approver, fields)
+    }
+
+    /// Global limits, overridden by the ones of the approver group when there is one
+    pub fn limits_for(&self, group: Option<&ApproverGroup>) -> ClientLimits {
+        let limits = self.limits.clone().unwrap_or_default();
+
+        let group_limits = group.and_then(|g| {
+            self.groups_limits
+                .as_ref()
+                .and_then(|groups| groups.get(g))
+        });
+
+        match group_limits {
+            Some(l) => limits.merge(l),
+            None => limits,
+        }
+    }
+}
+
+impl RedirectConfig {
+    /// Success page of the site/SSID, or the original URL when its host is allowed
+    pub fn target_for(&self, site: &str, ssid: Option<&str>, original: Option<&str>) -> Option<String> {
+        let page = self
+            .success_pages
+            .iter()
+            .find(|p| p.site == site && (p.ssid.is_none() || p.ssid.as_deref() == ssid));
+
+        if let Some(page) = page {
+            return Some(page.url.clone());
+        }
+
+        original
+            .filter(|url| self.is_allowed(url))
+            .map(String::from)
+    }
+
+    pub fn is_allowed(&self, url: &str) -> bool {
+        let Ok(url) = reqwest::Url::parse(url) else {
+            return false;
+        };
+        let Some(host) = url.host_str().map(|h| h.to_lowercase()) else {
+            return false;
+        };
+
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return false;
+        }
+
+        self.allowed_hosts.iter().any(|allowed| {
+            let allowed = allowed.to_lowercase();
+            match allowed.strip_prefix("*.") {
+                Some(domain) => host.ends_with(&format!(".{domain}")),
+                None => host == allowed,
+            }
+        })
+    }
+}
+
+impl UserGroupsConfig {
+    pub fn group_for(
+        &self,
+        user: Option<&str>,
+        approver: Option<&ApproverGroup>,
+        fields: &HashMap<String, String>,
+    ) -> Option<String> {
+        let by_user = user.and_then(|u| self.users.as_ref()?.get(u));
+        let by_approver = approver.and_then(|a| self.approvers.as_ref()?.get(a));
+        let by_field = self.fields.as_ref().and_then(|rules| {
+            rules
+                .iter()
+                .find(|r| fields.get(&r.field) == Some(&r.value))
+                .map(|r| &r.group)
+        });
+
+        by_user
+            .or(by_approver)
+            .or(by_field)
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
 impl DatabaseConfig {
     pub fn get_formated_url(&self) -> String {
         self.url
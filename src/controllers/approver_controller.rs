@@ -45,6 +45,7 @@ pub async fn create_approver(
         validity: None,
         secrete_code: approver.secrete_code,
         group: approver.group,
+        sites: approver.sites,
     };
     approver.create_validity(config.approvers.validity_days_code as i64);
 
@@ -53,12 +54,17 @@ pub async fn create_approver(
     Ok(Response::new_created(()))
 }
 
-#[get("/approver")]
+#[get("/approver?<site>")]
 pub async fn get_approvers(
     _admin: Admin,
     repository: MongoRepository<Approver>,
+    site: Option<String>,
 ) -> Result<Ok<Vec<Approver>>, Unauthorized> {
     let mut entitys = repository.find_all().await;
+    if let Some(site) = site {
+        entitys.retain(|a| a.allowed_on(&site));
+    }
+
     for i in 0..entitys.len() {
         let e = entitys.get_mut(i).unwrap();
         e.password = String::from("");
@@ -89,6 +95,7 @@ pub async fn update_approver(
     }
 
     approver.email = approver_data.email.unwrap_or(approver.email);
+    approver.sites = approver_data.sites.or(approver.sites);
     approver.password = approver_data
         .password
         .map(|p| hash(p, DEFAULT_COST).unwrap())
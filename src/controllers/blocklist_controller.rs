@@ -0,0 +1,77 @@
+use crate::{
+    model::{
+        entity::{
+            admin::Admin,
+            blocked_device::{BlockedDevice, BlockedDeviceData},
+        },
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    unifi::unifi::UnifiControllers,
+    utils::{
+        error::{CustomError, Error},
+        responses::{Created, Ok, Response},
+    },
+};
+use bson::doc;
+use rocket::{Route, delete, get, post, routes, serde::json::Json};
+
+// ENDPOINTS
+#[post("/blocklist", format = "application/json", data = "<data>")]
+pub async fn block_device(
+    admin: Admin,
+    unifi: UnifiControllers,
+    repository: MongoRepository<BlockedDevice>,
+    data: Json<BlockedDeviceData>,
+) -> Result<Created<BlockedDevice>, CustomError> {
+    let data = data.into_inner();
+    let entry = BlockedDevice::new_with_data(&data, admin.name);
+
+    unifi
+        .route(entry.controller.as_deref(), &entry.site)
+        .block_device(&entry.site, &entry.mac)
+        .await?;
+
+    match repository.save(entry).await {
+        Some(e) => Ok(Response::new_created(e)),
+        None => Err(Error::new_bad_request("Device could not be blocked")),
+    }
+}
+
+#[get("/blocklist?<site>")]
+pub async fn get_blocklist(
+    _admin: Admin,
+    repository: MongoRepository<BlockedDevice>,
+    site: Option<String>,
+) -> Result<Ok<Vec<BlockedDevice>>, CustomError> {
+    let entries = match site {
+        Some(site) => repository.find(doc! { "site": site }).await,
+        None => repository.find_all().await,
+    };
+
+    Ok(Response::new_ok(entries))
+}
+
+#[delete("/blocklist/<id>")]
+pub async fn unblock_device(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    repository: MongoRepository<BlockedDevice>,
+    id: String,
+) -> Result<Ok<()>, CustomError> {
+    let Some(entry) = repository.find_by_id(id).await else {
+        return Err(Error::new_not_found("Blocked device not found"));
+    };
+
+    unifi
+        .route(entry.controller.as_deref(), &entry.site)
+        .unblock_device(&entry.site, &entry.mac)
+        .await?;
+
+    repository.delete(entry).await;
+    Ok(Response::new_ok(()))
+}
+
+// Functions
+pub fn routes() -> Vec<Route> {
+    routes![block_device, get_blocklist, unblock_device]
+}
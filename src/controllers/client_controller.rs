@@ -2,20 +2,39 @@ use crate::configurations::config::ConfigApp;
 use crate::glpi::glpi::GLPI;
 use crate::model::entity::admin::Admin;
 use crate::model::entity::approver::{Approver, ApproverGroup};
-use crate::model::entity::client::{Client, ClientData, ClientInfo, ClientStatus};
+use crate::model::entity::blocked_device::BlockedDevice;
+use crate::model::entity::code_delivery::CodeDelivery;
+use crate::model::entity::portal_form::PortalForm;
+use crate::model::entity::terms::Terms;
+use crate::model::entity::verification::{Verification, VerificationChannel};
+use crate::model::entity::client::{
+    Client, ClientChangeKind, ClientData, ClientInfo, ClientStatus, ClientTimeUpdate,
+};
 use crate::model::repository::Repository;
 use crate::model::repository::mongo_repository::MongoRepository;
 use crate::security::approval_code::validate_code;
-use crate::unifi::unifi::UnifiController;
-use crate::utils::error::{BadRequest, CustomError, Error, NotFound, Unauthorized};
+use crate::security::blocklist::is_blocked;
+use crate::security::portal_session::{DEFAULT_MINUTES, PortalSession};
+use crate::security::verification::{confirm_code, send_email_code, send_sms_code};
+use crate::unifi::unifi::UnifiControllers;
+use crate::utils::client_events::ClientEvents;
+use crate::utils::terms::accept_terms;
+use crate::utils::mailer::Mailers;
+use crate::utils::sms::{SmsGateways, normalize_phone};
+use crate::utils::error::{CustomError, Error, NotFound, Unauthorized};
 use crate::utils::responses::{CustomStatus, Ok, Response};
-use chrono::Local;
+use bson::{doc, oid::ObjectId};
+use std::net::IpAddr;
+use chrono::{Duration, Local};
 use rocket::fs::NamedFile;
 use rocket::tokio::sync::RwLock;
 use rocket::http::CookieJar;
 use rocket::response::Redirect;
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
 use rocket::serde::json::Json;
-use rocket::{Route, State, get, post, put, routes};
+use rocket::{Route, Shutdown, State, delete, get, post, put, routes};
 
 // ENDPOINTS
 #[get("/<_..>")]
@@ -30,30 +49,46 @@ pub async fn client_connect_page(config: &State<ConfigApp>) -> Result<NamedFile,
 }
 
 #[get("/guest/s/<site>?<ap>&<id>&<t>&<url>&<ssid>", format = "text/html")]
+#[allow(clippy::too_many_arguments)]
 pub async fn client_register(
+    unifi: UnifiControllers,
     cookies: &CookieJar<'_>,
+    ip: Option<IpAddr>,
+    config: &State<ConfigApp>,
     site: String,
     ap: String,
     id: String,
     t: String,
     url: String,
     ssid: String,
-) -> Result<Redirect, ()> {
+) -> Result<Redirect, CustomError> {
     // /guest/s/default/?ap=70:a7:41:dd:7a:78&id=4c:eb:42:9b:82:55&t=1734714029&url=http://www.msftconnecttest.com%2Fredirect&ssid=Wi-Fi_Visitantes%20
+    let config = config.read().await;
+    let minutes = config.clients.portal_session_minutes.unwrap_or(DEFAULT_MINUTES);
+
+    if !t.parse().is_ok_and(|t| PortalSession::is_recent_redirect(t, minutes)) {
+        return Err(Error::new_bad_request("Portal redirect expired, reconnect to the Wi-Fi network"));
+    }
 
-    cookies.add(("ap", ap.clone()));
-    cookies.add(("id", id.clone()));
-    cookies.add(("t", t.clone()));
-    cookies.add(("ssid", ssid.clone()));
-    cookies.add(("site", site.clone()));
-    cookies.add(("url", url.clone()));
+    let mut session = PortalSession::new(id, site, Some(ap), Some(ssid), Some(url));
+
+    // The parameters come from the guest browser, so the device has to be on the site right now.
+    // The same site name can exist on several controllers, the guest belongs to the one it is found on
+    let ip = ip.filter(|_| config.clients.portal_check_ip.unwrap_or(true));
+    let Some(controller) = unifi.locate_station(&session.site, &session.mac, ip).await else {
+        return Err(Error::new_bad_request("Device not connected to the Wi-Fi network"));
+    };
+
+    session.controller = Some(controller.name().to_string());
+    session.save(cookies);
 
     Ok(Redirect::to("/client/"))
 }
 
 #[post("/client/connect", format = "application/json", data = "<data>")]
 pub async fn client_connection_api(
-    mut unifi: UnifiController,
+    unifi: UnifiControllers,
+    events: &State<ClientEvents>,
     repository: MongoRepository<Client>,
     data: Json<ClientInfo>,
     admin: Admin,
@@ -71,9 +106,13 @@ pub async fn client_connection_api(
                 c.status = ClientStatus::Approved;
                 c.start_time = Local::now();
 
-                let _ = unifi.conect_client(&c).await;
+                if let Some(limits) = &client.limits {
+                    c.limits = Some(c.limits.clone().unwrap_or_default().merge(limits));
+                }
+
+                unifi.for_client(&c).conect_client(&c).await?;
             } else {
-                let _ = unifi.reject_client(&c).await;
+                unifi.for_client(&c).reject_client(&c).await?;
                 c.status = ClientStatus::Reject;
                 
             }
@@ -88,7 +127,9 @@ pub async fn client_connection_api(
                 ).await;
             }
 
-            repository.update(c).await;
+            if let Some(c) = repository.update(c).await {
+                events.publish(&c);
+            }
             return Ok(Response::new_custom_status(200));
         }
     }
@@ -96,45 +137,115 @@ pub async fn client_connection_api(
     // Direct approval
     let mut new_client = Client::new_with_info(&client);
     new_client.approver = admin.name;
+    new_client.limits = Some(match &client.limits {
+        Some(limits) => config.clients.limits_for(None).merge(limits),
+        None => config.clients.limits_for(None),
+    });
+    new_client.user_group = config.clients.user_group_for(None, None, &new_client.fields);
 
     if client.connect {
-        unifi.conect_client(&new_client).await;
-        let _ = repository.save(new_client).await;
+        unifi.for_client(&new_client).conect_client(&new_client).await?;
+        if let Some(c) = repository.save(new_client).await {
+            events.publish(&c);
+        }
     } else {
-        let _ = unifi.unauthorize_device(&client.site, &client.mac).await;
+        unifi.route(client.controller.as_deref(), &client.site).unauthorize_device(&client.site, &client.mac).await?;
     };
 
     Ok(Response::new_custom_status(200))
 }
 
 #[post("/client/connect?form", format = "application/json", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn client_connection_approver(
-    mut unifi: UnifiController,
-    cookies: &CookieJar<'_>,
+    unifi: UnifiControllers,
+    events: &State<ClientEvents>,
+    portal: PortalSession,
     repository: MongoRepository<Client>,
     approver_repository: MongoRepository<Approver>,
+    blocklist_repository: MongoRepository<BlockedDevice>,
+    terms_repository: MongoRepository<Terms>,
+    verification_repository: MongoRepository<Verification>,
+    delivery_repository: MongoRepository<CodeDelivery>,
+    sms: &State<SmsGateways>,
+    mailer: &State<Mailers>,
+    ip: Option<IpAddr>,
     data: Json<ClientData>,
     config: &State<ConfigApp>,
     glpi: &State< RwLock<GLPI> > ,
-) -> Result<Ok<()>, BadRequest> {
+) -> Result<Ok<()>, CustomError> {
     let config = config.read().await; 
     let client = data.into_inner();
     
-    if !client.validate_form(config.clients.clone()) {
-        return Err(Error::new_bad_request("Invalid Form Field(s)"));
+    let form = config.clients.form_for(&portal.site, portal.ssid.as_deref());
+    if let Err(fields) = client.validate_form(&form) {
+        return Err(Error::new_invalid_fields(fields));
     }
-    
-    let mac = cookies.get("id").unwrap().value().to_string();
-    let site = cookies.get("site").unwrap().value().to_string();
+
+    let PortalSession { mac, site, controller, ap, .. } = portal;
     let minutes: u16 = config.clients.time_connection as u16;
+    let terms = accept_terms(&terms_repository, client.terms_version, &mac, ip).await?;
+
+    if is_blocked(&mac, &blocklist_repository).await {
+        return Err(Error::new_unauthorized("Device blocked"));
+    }
+
+    // Resolved before any code is sent, a guest who can't be approved gets no temporary access
+    let approver = match &client.approver_code {
+        Some(code) => validate_code(code.clone(), &approver_repository, config.approvers.encrypted_code)
+            .await
+            .filter(|a| a.allowed_on(&site))
+            .map(Some)
+            .ok_or_else(|| Error::new_bad_request("Invalid Fields"))?,
+        None if config.clients.free_request => None,
+        None => return Err(Error::new_bad_request("Invalid Fields")),
+    };
+
+    // The request is only saved once the guest proves the phone or the email is theirs
+    if let Some(verification) = &config.clients.sms_verification {
+        let phone = normalize_phone(&client.phone);
+
+        let Some(code) = &client.verification_code else {
+            // The text comes over the cellular network, the device stays off the Wi-Fi until verified
+            send_sms_code(&verification_repository, &delivery_repository, sms.inner().as_ref(), verification, &mac, &site, &phone).await?;
+            return Ok(Response::new_custom(202, ()));
+        };
+
+        confirm_code(&verification_repository, VerificationChannel::Sms, &mac, &phone, code).await?;
+    } else if let Some(verification) = &config.clients.email_verification {
+        let Some(code) = &client.verification_code else {
+            let Some(mailer) = mailer.inner() else {
+                return Err(Error::new_service_unavailable("Email verification unavailable"));
+            };
+
+            let first = send_email_code(&verification_repository, &delivery_repository, mailer.as_ref(), verification, &mac, &site, &client.email).await?;
+
+            // Long enough to open the mail, the real authorization comes with the approval. A new code
+            // does not extend it, or asking again would keep an unverified device online
+            if first {
+                let limits = config.clients.limits_for(None);
+                unifi.route(controller.as_deref(), &site).authorize_device(&site, &mac, verification.code_minutes, &limits).await?;
+            }
+
+            return Ok(Response::new_custom(202, ()));
+        };
+
+        confirm_code(&verification_repository, VerificationChannel::Email, &mac, &client.email, code).await?;
+    }
 
     let mut new_client = Client::new_with_data(&client);
     new_client.site = site.clone();
     new_client.mac = mac.clone();
+    new_client.controller = controller;
     new_client.time_connection = minutes.to_string();
+    new_client.ap = ap;
+    new_client.limits = Some(config.clients.limits_for(None));
+    new_client.user_group = config.clients.user_group_for(None, None, &new_client.fields);
+    new_client.terms = terms;
     
     let pending_client = async | new_client: Client | {
         let client = repository.save(new_client).await.unwrap();
+        events.publish(&client);
         
         if let Some(glpi_config) = &config.glpi {
             let mut glpi = glpi.write().await;
@@ -151,42 +262,43 @@ pub async fn client_connection_approver(
 
     };
 
-    if let Some(code) = &client.approver_code {
-        let approver = validate_code(code.clone(), &approver_repository, config.approvers.encrypted_code).await;
-            
-        if let Some(approver) = approver {
-            match approver.group {
-                ApproverGroup::AccessRelease => { 
-                    new_client.fields.insert("approved".to_string(), approver.username.clone());
-                    pending_client( new_client ).await;
-                },
+    if let Some(approver) = approver {
+        new_client.limits = Some(config.clients.limits_for(Some(&approver.group)));
+        new_client.user_group = config.clients.user_group_for(None, Some(&approver.group), &new_client.fields);
+
+        match approver.group {
+            ApproverGroup::AccessRelease => { 
+                new_client.fields.insert("approved".to_string(), approver.username.clone());
+                pending_client( new_client ).await;
+            },
 
-                ApproverGroup::DirectApproval => { 
-                    new_client.status = ClientStatus::Approved;
-                    new_client.approver = approver.username.clone();
-                        
-                    unifi.conect_client( &new_client ).await;
-                    let _ = repository.save(new_client).await;
+            ApproverGroup::DirectApproval => { 
+                new_client.status = ClientStatus::Approved;
+                new_client.approver = approver.username.clone();
+                    
+                unifi.for_client(&new_client).conect_client( &new_client ).await?;
+                if let Some(c) = repository.save(new_client).await {
+                    events.publish(&c);
                 }
-            }   
-        } 
-        else { return Err( Error::new_bad_request("Invalid Fields") ); }
+            }
+        }   
     }
 
-    else {
-        if !config.clients.free_request { return Err(Error::new_bad_request("Invalid Fields")); }
-        pending_client( new_client ).await;
-    } 
+    else { pending_client( new_client ).await; }
 
     Ok( Response::new_ok(()) )
 }
 
-#[get("/client", format = "application/json")]
+#[get("/client?<site>", format = "application/json")]
 pub async fn get_clients(
     _admin: Admin,
     client_repo: MongoRepository<Client>,
+    site: Option<String>,
 ) -> Result<Ok<Vec<Client>>, Unauthorized> {
-    let clients = client_repo.find_all().await;
+    let clients = match site {
+        Some(site) => client_repo.find(doc! { "site": site }).await,
+        None => client_repo.find_all().await,
+    };
 
     Ok(Response::new_ok(clients))
 }
@@ -201,30 +313,173 @@ pub async fn update_client(
     Ok(Response::new_ok(()))
 }
 
-#[get("/client/<mac>/status")]
+#[get("/client/<mac>/status?<site>")]
 pub async fn get_client_status(
     client_repo: MongoRepository<Client>,
     mac: String,
+    site: Option<String>,
 ) -> Result<Ok<ClientStatus>, NotFound> {
-    let clients = client_repo.find_all().await;
-    let mut client: Option<Client> = None;
+    match latest_client(&client_repo, &mac.to_lowercase(), site.as_deref()).await {
+        Some(c) => Ok( Response::new_ok(c.status) ),
+        None => Err( Error::new_not_found("Device not found") )
+    }
+}
 
-    for c in clients.iter() {
-        if c.mac == mac { 
-            if let Some(cl) = &client {
-                if c.start_time >= cl.start_time { client = Some(c.clone()); }
-            } else {
-                client = Some(c.clone());
+#[get("/client/status/events")]
+pub async fn client_status_events(
+    portal: PortalSession,
+    client_repo: MongoRepository<Client>,
+    events: &State<ClientEvents>,
+    mut shutdown: Shutdown,
+) -> EventStream![] {
+    // Subscribing before reading the record, so a change in between is not lost
+    let mut receiver = events.subscribe();
+    let current = latest_client(&client_repo, &portal.mac, Some(&portal.site)).await;
+
+    EventStream! {
+        if let Some(c) = current {
+            yield Event::json(&c.status).event("status");
+        }
+
+        loop {
+            let event = select! {
+                res = receiver.recv() => match res {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            if event.mac == portal.mac && event.site == portal.site {
+                yield Event::json(&event.status).event("status");
             }
         }
     }
-    
-    match client {
-        Some(c) => Ok( Response::new_ok(c.status) ),
-        None => Err( Error::new_not_found("Device not found") )
+}
+
+#[get("/form/<site>?<ssid>")]
+pub async fn get_client_form(
+    config: &State<ConfigApp>,
+    site: &str,
+    ssid: Option<&str>,
+) -> Ok<PortalForm> {
+    let config = config.read().await;
+    Response::new_ok(config.clients.form_for(site, ssid))
+}
+
+#[get("/client/redirect")]
+pub async fn get_client_redirect(
+    portal: PortalSession,
+    client_repo: MongoRepository<Client>,
+    config: &State<ConfigApp>,
+) -> Result<Ok<String>, CustomError> {
+    let config = config.read().await;
+
+    let approved = latest_client(&client_repo, &portal.mac, Some(&portal.site))
+        .await
+        .is_some_and(|c| c.status == ClientStatus::Approved);
+
+    if !approved {
+        return Err(Error::new_bad_request("Client not approved"));
+    }
+
+    let target = config
+        .clients
+        .redirect
+        .as_ref()
+        .and_then(|r| r.target_for(&portal.site, portal.ssid.as_deref(), portal.url.as_deref()));
+
+    match target {
+        Some(url) => Ok(Response::new_ok(url)),
+        None => Err(Error::new_not_found("No redirect for this session")),
     }
 }
 
+#[put("/client/<id>/time", format = "application/json", data = "<data>")]
+pub async fn update_client_time(
+    admin: Admin,
+    unifi: UnifiControllers,
+    events: &State<ClientEvents>,
+    client_repo: MongoRepository<Client>,
+    id: String,
+    data: Json<ClientTimeUpdate>,
+) -> Result<Ok<Client>, CustomError> {
+    let Some(client) = client_repo.find_by_id(id).await else {
+        return Err(Error::new_not_found("Client not found"));
+    };
+
+    let client = change_session_time(&unifi, client, data.minutes, admin.name).await?;
+    save_session(&client_repo, &client).await?;
+    events.publish(&client);
+
+    Ok(Response::new_ok(client))
+}
+
+#[put("/client/<id>/time?approver", format = "application/json", data = "<data>")]
+pub async fn update_client_time_approver(
+    unifi: UnifiControllers,
+    events: &State<ClientEvents>,
+    client_repo: MongoRepository<Client>,
+    approver_repository: MongoRepository<Approver>,
+    id: String,
+    data: Json<ClientTimeUpdate>,
+    config: &State<ConfigApp>,
+) -> Result<Ok<Client>, CustomError> {
+    let config = config.read().await;
+    let data = data.into_inner();
+
+    // Approvers can only extend sessions, shortening is left to the admins
+    let too_long = config.approvers.max_extension_minutes.is_some_and(|max| data.minutes > max);
+    if data.minutes <= 0 || too_long {
+        return Err(Error::new_bad_request("Invalid Fields"));
+    }
+
+    let Some(client) = client_repo.find_by_id(id).await else {
+        return Err(Error::new_not_found("Client not found"));
+    };
+
+    let approver = match &data.approver_code {
+        Some(code) => validate_code(code.clone(), &approver_repository, config.approvers.encrypted_code)
+            .await
+            .filter(|a| a.allowed_on(&client.site)),
+        None => None,
+    };
+
+    let Some(approver) = approver else {
+        return Err(Error::new_bad_request("Invalid Fields"));
+    };
+
+    let client = change_session_time(&unifi, client, data.minutes, approver.username).await?;
+    save_session(&client_repo, &client).await?;
+    events.publish(&client);
+
+    Ok(Response::new_ok(client))
+}
+
+#[delete("/client/<id>/session")]
+pub async fn end_client_session(
+    admin: Admin,
+    unifi: UnifiControllers,
+    events: &State<ClientEvents>,
+    client_repo: MongoRepository<Client>,
+    id: String,
+) -> Result<Ok<Client>, CustomError> {
+    let Some(mut client) = client_repo.find_by_id(id).await else {
+        return Err(Error::new_not_found("Client not found"));
+    };
+
+    if client.status != ClientStatus::Approved {
+        return Err(Error::new_bad_request("Client session is not active"));
+    }
+
+    end_session(&unifi, &mut client, admin.name).await?;
+    save_session(&client_repo, &client).await?;
+    events.publish(&client);
+
+    Ok(Response::new_ok(client))
+}
+
 // Functions
 pub fn routes() -> Vec<Route> {
     routes![
@@ -233,5 +488,93 @@ pub fn routes() -> Vec<Route> {
         get_clients,
         update_client,
         get_client_status,
+        client_status_events,
+        get_client_form,
+        get_client_redirect,
+        update_client_time,
+        update_client_time_approver,
+        end_client_session,
     ]
 }
+
+// Newest record of the device, on the site when known, served by the { mac, site, start_time } index
+async fn latest_client(client_repo: &MongoRepository<Client>, mac: &str, site: Option<&str>) -> Option<Client> {
+    let mut query = doc! { "mac": mac };
+    if let Some(site) = site {
+        query.insert("site", site);
+    }
+
+    client_repo.find_first(query, doc! { "start_time": -1 }).await
+}
+
+// Re-issues the authorization with the new remaining time, or ends it when nothing is left
+async fn change_session_time(
+    unifi: &UnifiControllers,
+    mut client: Client,
+    minutes: i64,
+    by: String,
+) -> Result<Client, CustomError> {
+    if client.status != ClientStatus::Approved {
+        return Err(Error::new_bad_request("Client session is not active"));
+    }
+
+    let now = Local::now();
+    let end = client.end_time();
+    let mut new_end = end + Duration::minutes(minutes);
+    let remaining = (new_end - now).num_minutes();
+
+    if remaining <= 0 {
+        end_session(unifi, &mut client, by).await?;
+        return Ok(client);
+    }
+
+    // The controller takes at most u16::MAX minutes, the record keeps the same end
+    if remaining > u16::MAX as i64 {
+        new_end = now + Duration::minutes(u16::MAX as i64);
+    }
+
+    let limits = client.limits.clone().unwrap_or_default();
+    unifi
+        .for_client(&client)
+        .authorize_device(&client.site, &client.mac, (new_end - now).num_minutes() as u16, &limits)
+        .await?;
+
+    let minutes = (new_end - end).num_minutes();
+    let kind = if minutes >= 0 { ClientChangeKind::Extended } else { ClientChangeKind::Shortened };
+    client.time_connection = (new_end - client.start_time).num_minutes().to_string();
+    client.record_change(kind, minutes, by);
+
+    Ok(client)
+}
+
+// Writes only the session fields, so the monitoring writes made meanwhile are kept
+async fn save_session(client_repo: &MongoRepository<Client>, client: &Client) -> Result<(), CustomError> {
+    let Ok(id) = ObjectId::parse_str(&client.id) else {
+        return Err(Error::new_not_found("Client not found"));
+    };
+
+    let fields = doc! {
+        "time_connection": &client.time_connection,
+        "status": bson::to_bson(&client.status).unwrap(),
+        "changes": bson::to_bson(&client.changes).unwrap(),
+    };
+
+    match client_repo.update_all(doc! { "_id": id }, doc! { "$set": fields }).await {
+        0 => Err(Error::new_internal_server_error("Client could not be saved")),
+        _ => Ok(()),
+    }
+}
+
+async fn end_session(unifi: &UnifiControllers, client: &mut Client, by: String) -> Result<(), CustomError> {
+    unifi
+        .for_client(client)
+        .unauthorize_device(&client.site, &client.mac)
+        .await?;
+
+    let remaining = (client.end_time() - Local::now()).num_minutes().max(0);
+    client.time_connection = (Local::now() - client.start_time).num_minutes().to_string();
+    client.status = ClientStatus::Expired;
+    client.record_change(ClientChangeKind::Ended, -remaining, by);
+
+    Ok(())
+}
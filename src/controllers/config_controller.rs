@@ -45,7 +45,11 @@ pub async fn update_configs(
         config.ldap = Some(ldap)
     } 
 
-    if data.server.is_some() || data.unifi.is_some() || data.database.is_some() || data.ldap.is_some() {
+    if let Some(wlans) = data.wlans.clone() {
+        config.wlans = Some(wlans)
+    }
+
+    if data.server.is_some() || data.unifi.is_some() || data.database.is_some() || data.ldap.is_some() || data.wlans.is_some() {
         tokio::spawn(async {
             sleep(Duration::from_secs(5)).await;
             process::exit(0);
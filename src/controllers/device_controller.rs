@@ -0,0 +1,35 @@
+use crate::{
+    model::entity::admin::Admin,
+    unifi::unifi::{AccessPoint, UnifiControllers},
+    utils::{
+        error::CustomError,
+        responses::{Ok, Response},
+    },
+};
+use rocket::{Route, get, routes};
+
+// ENDPOINTS
+#[get("/device/<site>")]
+pub async fn get_network_devices(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    site: String,
+) -> Result<Ok<Vec<AccessPoint>>, CustomError> {
+    let devices = unifi.for_site(&site).get_network_devices(&site).await?;
+    Ok(Response::new_ok(devices))
+}
+
+#[get("/ap/<site>")]
+pub async fn get_access_points(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    site: String,
+) -> Result<Ok<Vec<AccessPoint>>, CustomError> {
+    let aps = unifi.for_site(&site).get_access_points(&site).await?;
+    Ok(Response::new_ok(aps))
+}
+
+// Functions
+pub fn routes() -> Vec<Route> {
+    routes![get_network_devices, get_access_points]
+}
@@ -1,3 +1,4 @@
+use crate::security::portal_session::PortalSessionError;
 use crate::utils::error::{BadRequest, Error, NotFound};
 use rocket::{Catcher, Request, catch, catchers};
 
@@ -18,8 +19,11 @@ fn api_unauthorized(_req: &Request) -> NotFound {
 }
 
 #[catch(400)]
-fn api_bad_request(_req: &Request) -> BadRequest {
-    Error::new_bad_request("Invalid request body")
+fn api_bad_request(req: &Request) -> BadRequest {
+    match req.local_cache(|| None::<PortalSessionError>) {
+        Some(PortalSessionError(msg)) => Error::new_bad_request(msg),
+        None => Error::new_bad_request("Invalid request body"),
+    }
 }
 
 // Functions
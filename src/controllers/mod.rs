@@ -1,6 +1,13 @@
 pub mod admin_controller;
 pub mod approver_controller;
+pub mod blocklist_controller;
 pub mod client_controller;
 pub mod config_controller;
+pub mod device_controller;
 pub mod error_controller;
+pub mod session_controller;
+pub mod site_controller;
+pub mod terms_controller;
 pub mod user_controller;
+pub mod voucher_controller;
+pub mod wlan_controller;
@@ -0,0 +1,78 @@
+use crate::{
+    model::{
+        entity::{
+            admin::Admin,
+            session::{Session, SessionTraffic},
+        },
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    utils::{
+        error::{CustomError, Error, Unauthorized},
+        responses::{Ok, Response},
+    },
+};
+use bson::{Document, doc};
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use rocket::{Route, get, routes};
+use std::cmp::Reverse;
+
+// ENDPOINTS
+#[get("/client/<id>/sessions")]
+pub async fn get_client_sessions(
+    _admin: Admin,
+    session_repo: MongoRepository<Session>,
+    id: String,
+) -> Result<Ok<Vec<Session>>, Unauthorized> {
+    let mut sessions = session_repo.find(doc! { "client_id": id }).await;
+    sessions.sort_by_key(|s| Reverse(s.start));
+
+    Ok(Response::new_ok(sessions))
+}
+
+/// `from` and `to` are days (YYYY-MM-DD), both included. Without `from` the last `days` are read
+#[get("/session/traffic?<site>&<mac>&<days>&<from>&<to>")]
+pub async fn get_traffic(
+    _admin: Admin,
+    session_repo: MongoRepository<Session>,
+    site: Option<String>,
+    mac: Option<String>,
+    days: Option<i64>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<Ok<Vec<SessionTraffic>>, CustomError> {
+    let mut query = Document::new();
+    if let Some(site) = site {
+        query.insert("site", site);
+    }
+    if let Some(mac) = mac {
+        query.insert("mac", mac.to_lowercase());
+    }
+
+    let since = match from {
+        Some(day) => start_of(&day)?,
+        None => Local::now() - Duration::days(days.unwrap_or(30)),
+    };
+
+    // Dates are stored as RFC 3339 strings, so the bounds are written the same way to compare them
+    let mut start = doc! { "$gte": bson::to_bson(&since).unwrap() };
+    if let Some(day) = to {
+        start.insert("$lt", bson::to_bson(&(start_of(&day)? + Duration::days(1))).unwrap());
+    }
+    query.insert("start", start);
+
+    let sessions: Vec<Session> = session_repo.find(query).await;
+
+    Ok(Response::new_ok(SessionTraffic::new_with_sessions(&sessions)))
+}
+
+// Functions
+pub fn routes() -> Vec<Route> {
+    routes![get_client_sessions, get_traffic]
+}
+
+fn start_of(day: &str) -> Result<DateTime<Local>, CustomError> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest())
+        .ok_or(Error::new_bad_request("Invalid date, use YYYY-MM-DD"))
+}
@@ -0,0 +1,106 @@
+use crate::{
+    model::{
+        entity::{
+            admin::Admin, client::Client, guest_portal::GuestPortal, reconciliation::Reconciliation,
+            site::SiteStats,
+        },
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    unifi::unifi::{Site, UnifiControllers, UserGroup},
+    utils::{
+        error::{CustomError, Error, NotFound},
+        responses::{Ok, Response},
+    },
+};
+use bson::doc;
+use rocket::{Route, get, put, routes, serde::json::Json};
+
+// ENDPOINTS
+#[get("/site")]
+pub async fn get_sites(
+    _admin: Admin,
+    unifi: UnifiControllers,
+) -> Result<Ok<Vec<Site>>, CustomError> {
+    let sites = unifi.get_sites().await?;
+    Ok(Response::new_ok(sites))
+}
+
+#[get("/site/<site>/stats")]
+pub async fn get_site_stats(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    client_repo: MongoRepository<Client>,
+    site: String,
+) -> Result<Ok<SiteStats>, CustomError> {
+    let clients = client_repo.find(doc! { "site": site.clone() }).await;
+    let mut stats = SiteStats::new_with_clients(site.clone(), &clients);
+
+    stats.guests_connected = unifi.for_site(&site).get_all_devices(site.clone(), true).await?.len();
+
+    Ok(Response::new_ok(stats))
+}
+
+#[get("/site/<site>/reconciliation")]
+pub async fn get_site_reconciliation(
+    _admin: Admin,
+    reconciliation_repo: MongoRepository<Reconciliation>,
+    site: String,
+) -> Result<Ok<Reconciliation>, NotFound> {
+    match reconciliation_repo.find_one(doc! { "site": site }).await {
+        Some(report) => Ok(Response::new_ok(report)),
+        None => Err(Error::new_not_found("Site not reconciled yet")),
+    }
+}
+
+#[get("/site/<site>/portal")]
+pub async fn get_site_portal(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    site: String,
+) -> Result<Ok<GuestPortal>, CustomError> {
+    let setting = unifi.for_site(&site).get_guest_access(&site).await?;
+    Ok(Response::new_ok(GuestPortal::new_with_setting(&setting)))
+}
+
+#[put("/site/<site>/portal", format = "application/json", data = "<data>")]
+pub async fn update_site_portal(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    site: String,
+    data: Json<GuestPortal>,
+) -> Result<Ok<GuestPortal>, CustomError> {
+    let portal = data.into_inner();
+
+    if let Err(msg) = portal.validate() {
+        return Err(Error::new_bad_request(&msg));
+    }
+
+    let unifi = unifi.for_site(&site);
+    let mut setting = unifi.get_guest_access(&site).await?;
+    portal.apply(&mut setting);
+
+    let setting = unifi.set_guest_access(&site, &setting).await?;
+    Ok(Response::new_ok(GuestPortal::new_with_setting(&setting)))
+}
+
+#[get("/site/<site>/usergroup")]
+pub async fn get_site_user_groups(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    site: String,
+) -> Result<Ok<Vec<UserGroup>>, CustomError> {
+    let groups = unifi.for_site(&site).get_user_groups(&site).await?;
+    Ok(Response::new_ok(groups))
+}
+
+// Functions
+pub fn routes() -> Vec<Route> {
+    routes![
+        get_sites,
+        get_site_stats,
+        get_site_reconciliation,
+        get_site_portal,
+        update_site_portal,
+        get_site_user_groups
+    ]
+}
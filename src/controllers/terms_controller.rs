@@ -0,0 +1,59 @@
+use crate::{
+    model::{
+        entity::{
+            admin::Admin,
+            terms::{Terms, TermsData},
+        },
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    utils::{
+        error::{CustomError, Error, NotFound},
+        responses::{Created, Ok, Response},
+        terms::current_terms,
+    },
+};
+use rocket::{Route, get, post, routes, serde::json::Json};
+use std::cmp::Reverse;
+
+// ENDPOINTS
+#[get("/terms")]
+pub async fn get_current_terms(repository: MongoRepository<Terms>) -> Result<Ok<Terms>, NotFound> {
+    match current_terms(&repository).await {
+        Some(terms) => Ok(Response::new_ok(terms)),
+        None => Err(Error::new_not_found("No terms published")),
+    }
+}
+
+#[get("/terms/history")]
+pub async fn get_terms_history(_admin: Admin, repository: MongoRepository<Terms>) -> Ok<Vec<Terms>> {
+    let mut versions = repository.find_all().await;
+    versions.sort_by_key(|t| Reverse(t.version));
+
+    Response::new_ok(versions)
+}
+
+/// Publishes a new version, guests have to accept it again on their next connection
+#[post("/terms", format = "application/json", data = "<data>")]
+pub async fn publish_terms(
+    admin: Admin,
+    repository: MongoRepository<Terms>,
+    data: Json<TermsData>,
+) -> Result<Created<Terms>, CustomError> {
+    let data = data.into_inner();
+    if data.content.trim().is_empty() {
+        return Err(Error::new_bad_request("Invalid Fields"));
+    }
+
+    let version = current_terms(&repository).await.map_or(1, |t| t.version + 1);
+    let terms = Terms::new_with_data(&data, version, admin.name);
+
+    match repository.save(terms).await {
+        Some(t) => Ok(Response::new_created(t)),
+        None => Err(Error::new_bad_request("Terms could not be published")),
+    }
+}
+
+// Functions
+pub fn routes() -> Vec<Route> {
+    routes![get_current_terms, get_terms_history, publish_terms]
+}
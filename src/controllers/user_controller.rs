@@ -4,20 +4,26 @@ use crate::{
     model::{
         entity::{
             admin::Admin,
+            blocked_device::BlockedDevice,
             client::{Client, ClientStatus},
+            terms::Terms,
             user::{User, UserLogin, UserUpdate},
         },
         repository::{mongo_repository::MongoRepository, Repository},
     },
-    unifi::unifi::UnifiController,
+    security::{blocklist::is_blocked, portal_session::PortalSession},
+    unifi::unifi::UnifiControllers,
     utils::{
-        error::{BadRequest, Error, NotFound, Unauthorized},
+        client_events::ClientEvents,
+        error::{BadRequest, CustomError, Error, NotFound, Unauthorized},
         responses::{Accepted, Created, Ok, Response},
+        terms::accept_terms,
     },
 };
 use bcrypt::{DEFAULT_COST, hash, verify};
 use bson::doc;
-use rocket::{Route, State, delete, get, http::CookieJar, post, put, routes, serde::json::Json};
+use std::net::IpAddr;
+use rocket::{Route, State, delete, get, post, put, routes, serde::json::Json};
 
 // Endpoints
 #[post("/user", data = "<data>")]
@@ -55,14 +61,19 @@ pub async fn create_user(
 }
 
 #[post("/user/login", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn login_user(
     data: Json<UserLogin>,
-    cookies: &CookieJar<'_>,
-    mut unifi: UnifiController,
+    portal: PortalSession,
+    events: &State<ClientEvents>,
+    unifi: UnifiControllers,
     user_repo: MongoRepository<User>,
     client_repo: MongoRepository<Client>,
+    blocklist_repo: MongoRepository<BlockedDevice>,
+    terms_repo: MongoRepository<Terms>,
+    ip: Option<IpAddr>,
     config: &State<ConfigApp>,
-) -> Result<Accepted<String>, BadRequest> {
+) -> Result<Accepted<String>, CustomError> {
     let config = config.read().await;
 
     match user_repo
@@ -90,17 +101,28 @@ pub async fn login_user(
 
             let mut new_client = Client::new_with_data(&user.data);
 
-            let mac = cookies.get("id").unwrap().value().to_string();
-            let site = cookies.get("site").unwrap().value().to_string();
+            let PortalSession { mac, site, controller, ap, .. } = portal;
+            let terms = accept_terms(&terms_repo, data.terms_version, &mac, ip).await?;
+
+            if is_blocked(&mac, &blocklist_repo).await {
+                return Err(Error::new_unauthorized("Device blocked"));
+            }
             let minutes: u16 = config.clients.time_connection as u16;
 
             new_client.site = site.clone();
             new_client.mac = mac.clone();
+            new_client.controller = controller;
+            new_client.ap = ap;
             new_client.time_connection = minutes.to_string();
             new_client.status = ClientStatus::Approved;
+            new_client.limits = Some(config.clients.limits_for(None));
+            new_client.user_group = config.clients.user_group_for(Some(&user.username), None, &new_client.fields);
+            new_client.terms = terms;
 
-            unifi.conect_client(&new_client).await;
-            let _ = client_repo.save(new_client).await;
+            unifi.for_client(&new_client).conect_client(&new_client).await?;
+            if let Some(c) = client_repo.save(new_client).await {
+                events.publish(&c);
+            }
 
             Ok(Response::new_accepted(String::from("Connection Approved")))
         }
@@ -0,0 +1,193 @@
+use crate::{
+    configurations::config::ConfigApp,
+    model::{
+        entity::{
+            admin::Admin,
+            blocked_device::BlockedDevice,
+            client::{Client, ClientStatus},
+            terms::Terms,
+            voucher::{VoucherData, VoucherRedeem},
+            voucher_attempt::VoucherAttempt,
+            voucher_redemption::VoucherRedemption,
+        },
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    security::{blocklist::is_blocked, portal_session::PortalSession, verification::MAX_ATTEMPTS},
+    unifi::unifi::{UnifiControllers, Voucher, VoucherCreation},
+    utils::{
+        client_events::ClientEvents,
+        error::{CustomError, Error},
+        responses::{Created, Ok, Response},
+        terms::accept_terms,
+    },
+};
+use bson::{DateTime, doc};
+use std::net::IpAddr;
+use rocket::{Route, State, delete, get, post, routes, serde::json::Json};
+
+/// Time the wrong voucher codes of a device are counted
+const ATTEMPT_WINDOW_MINUTES: i64 = 15;
+
+// ENDPOINTS
+#[post("/voucher", format = "application/json", data = "<data>")]
+pub async fn create_vouchers(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    data: Json<VoucherData>,
+) -> Result<Created<Vec<Voucher>>, CustomError> {
+    let data = data.into_inner();
+
+    if data.count == 0 || data.minutes == 0 {
+        return Err(Error::new_bad_request("Invalid Field(s)"));
+    }
+
+    let voucher = VoucherCreation::new(
+        data.count,
+        data.minutes,
+        data.quota,
+        data.note,
+        &data.limits.unwrap_or_default(),
+    );
+
+    let vouchers = unifi.for_site(&data.site).create_vouchers(&data.site, voucher).await?;
+    Ok(Response::new_created(vouchers))
+}
+
+#[get("/voucher/<site>")]
+pub async fn get_vouchers(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    site: String,
+) -> Result<Ok<Vec<Voucher>>, CustomError> {
+    let vouchers = unifi.for_site(&site).get_vouchers(&site).await?;
+    Ok(Response::new_ok(vouchers))
+}
+
+#[delete("/voucher/<site>/<id>")]
+pub async fn revoke_voucher(
+    _admin: Admin,
+    unifi: UnifiControllers,
+    site: String,
+    id: String,
+) -> Result<Ok<()>, CustomError> {
+    unifi.for_site(&site).revoke_voucher(&site, id).await?;
+    Ok(Response::new_ok(()))
+}
+
+#[post("/client/voucher", format = "application/json", data = "<data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn redeem_voucher(
+    unifi: UnifiControllers,
+    portal: PortalSession,
+    events: &State<ClientEvents>,
+    repository: MongoRepository<Client>,
+    blocklist_repository: MongoRepository<BlockedDevice>,
+    terms_repository: MongoRepository<Terms>,
+    redemption_repository: MongoRepository<VoucherRedemption>,
+    attempt_repository: MongoRepository<VoucherAttempt>,
+    ip: Option<IpAddr>,
+    data: Json<VoucherRedeem>,
+    config: &State<ConfigApp>,
+) -> Result<Ok<()>, CustomError> {
+    let config = config.read().await;
+    let data = data.into_inner();
+    let code = data.normalized_code();
+
+    let PortalSession { mac, site, controller, ap, .. } = portal;
+    let terms = accept_terms(&terms_repository, data.terms_version, &mac, ip).await?;
+
+    if is_blocked(&mac, &blocklist_repository).await {
+        return Err(Error::new_unauthorized("Device blocked"));
+    }
+
+    let attempts = attempt_repository
+        .find(doc! { "mac": &mac, "expires_at": { "$gt": DateTime::now() } })
+        .await
+        .len();
+
+    if attempts >= MAX_ATTEMPTS as usize {
+        return Err(Error::new_too_many_requests("Too many attempts, try again later"));
+    }
+
+    let unifi = unifi.route(controller.as_deref(), &site);
+    let voucher = unifi
+        .get_vouchers(&site)
+        .await?
+        .into_iter()
+        .find(|v| v.code == code);
+
+    let Some(voucher) = voucher else {
+        attempt_repository.save(VoucherAttempt::new(&mac, &site, ATTEMPT_WINDOW_MINUTES)).await;
+        return Err(Error::new_bad_request("Invalid Voucher"));
+    };
+
+    // Authorizing through the API does not count as a use on the controller, those are reserved
+    // here before the guest is authorized
+    let quota = voucher.quota.unwrap_or(1);
+    let used = voucher.used.unwrap_or(0);
+    let key = doc! { "site": &site, "controller": &controller, "code": &code };
+
+    if quota != 0 && used >= quota {
+        return Err(Error::new_bad_request("Voucher already used"));
+    }
+
+    let uses = match quota {
+        0 => used,
+        _ => {
+            let mut query = key.clone();
+            query.insert("uses", doc! { "$lt": quota - used });
+
+            match redemption_repository.upsert_one(query, doc! { "$inc": { "uses": 1 } }).await {
+                Some(redemption) => used + redemption.uses,
+                None => return Err(Error::new_bad_request("Voucher already used")),
+            }
+        }
+    };
+
+    let mut new_client = match &data.data {
+        Some(d) => Client::new_with_data(d),
+        None => Client::new(),
+    };
+
+    let minutes = voucher
+        .duration
+        .unwrap_or(config.clients.time_connection as u32)
+        .min(u16::MAX as u32);
+
+    new_client.site = site.clone();
+    new_client.mac = mac;
+    new_client.controller = controller;
+    new_client.ap = ap;
+    new_client.time_connection = minutes.to_string();
+    new_client.status = ClientStatus::Approved;
+    new_client.approver = format!("voucher:{code}");
+    new_client.limits = Some(config.clients.limits_for(None).merge(&voucher.limits()));
+    new_client.user_group = config.clients.user_group_for(None, None, &new_client.fields);
+    new_client.terms = terms;
+
+    if let Err(e) = unifi.conect_client(&new_client).await {
+        if quota != 0 {
+            redemption_repository.update_all(key, doc! { "$inc": { "uses": -1 } }).await;
+        }
+
+        return Err(e.into());
+    }
+
+    if let Some(c) = repository.save(new_client).await {
+        events.publish(&c);
+    }
+
+    if quota != 0
+        && uses >= quota
+        && let Err(e) = unifi.revoke_voucher(&site, voucher.id.clone()).await
+    {
+        println!("{e}");
+    }
+
+    Ok(Response::new_ok(()))
+}
+
+// Functions
+pub fn routes() -> Vec<Route> {
+    routes![create_vouchers, get_vouchers, revoke_voucher, redeem_voucher]
+}
@@ -0,0 +1,110 @@
+use crate::{
+    configurations::config::{ConfigApp, WlanRotation},
+    model::{
+        entity::{admin::Admin, psk_rotation::PskRotation},
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    security::kiosk::KioskToken,
+    unifi::unifi::UnifiControllers,
+    utils::{
+        error::{CustomError, Error, Unauthorized},
+        responses::{Created, Ok, Response},
+        wlan::{current_passphrases, rotate_passphrase},
+    },
+};
+use bson::{Document, doc};
+use rocket::{Route, State, get, post, routes};
+use std::cmp::Reverse;
+
+// ENDPOINTS
+#[get("/wlan")]
+pub async fn get_passphrases(
+    _admin: Admin,
+    repo: MongoRepository<PskRotation>,
+    config: &State<ConfigApp>,
+) -> Result<Ok<Vec<PskRotation>>, Unauthorized> {
+    let config = config.read().await;
+
+    let current = match &config.wlans {
+        Some(wlans) => current_passphrases(&repo, wlans).await,
+        None => vec![],
+    };
+
+    Ok(Response::new_ok(current))
+}
+
+#[get("/wlan/history?<site>&<ssid>")]
+pub async fn get_rotation_history(
+    _admin: Admin,
+    repo: MongoRepository<PskRotation>,
+    site: Option<String>,
+    ssid: Option<String>,
+) -> Result<Ok<Vec<PskRotation>>, Unauthorized> {
+    let mut query = Document::new();
+    if let Some(site) = site {
+        query.insert("site", site);
+    }
+    if let Some(ssid) = ssid {
+        query.insert("ssid", ssid);
+    }
+
+    let mut history = repo.find(query).await;
+    history.sort_by_key(|r| Reverse(r.rotated_at));
+
+    Ok(Response::new_ok(history))
+}
+
+#[post("/wlan/<site>/<ssid>/rotate")]
+pub async fn rotate_wlan(
+    admin: Admin,
+    unifi: UnifiControllers,
+    repo: MongoRepository<PskRotation>,
+    config: &State<ConfigApp>,
+    site: String,
+    ssid: String,
+) -> Result<Created<PskRotation>, CustomError> {
+    let config = config.read().await;
+
+    let Some(wlans) = &config.wlans else {
+        return Err(Error::new_bad_request("WLAN rotation is not configured"));
+    };
+
+    let target = WlanRotation { site, ssid };
+    if !wlans.rotate.iter().any(|w| w.site == target.site && w.ssid == target.ssid) {
+        return Err(Error::new_not_found("WLAN not configured for rotation"));
+    }
+
+    let rotation = rotate_passphrase(unifi.for_site(&target.site), &repo, wlans, &target, admin.name).await?;
+    Ok(Response::new_created(rotation))
+}
+
+// Reception kiosk, shows the current passphrases with the configured token instead of an admin login
+#[get("/wlan/kiosk?<site>")]
+pub async fn get_kiosk_passphrases(
+    token: KioskToken,
+    repo: MongoRepository<PskRotation>,
+    config: &State<ConfigApp>,
+    site: Option<String>,
+) -> Result<Ok<Vec<PskRotation>>, Unauthorized> {
+    let config = config.read().await;
+
+    let Some(wlans) = &config.wlans else {
+        return Err(Error::new_unauthorized("Kiosk disabled"));
+    };
+
+    if !wlans.kiosk_token.as_deref().is_some_and(|t| token.matches(t)) {
+        return Err(Error::new_unauthorized("Invalid token"));
+    }
+
+    let mut current = current_passphrases(&repo, wlans).await;
+    if let Some(site) = site {
+        current.retain(|r| r.site == site);
+    }
+
+    Ok(Response::new_ok(current))
+}
+
+// Functions
+pub fn routes() -> Vec<Route> {
+    routes![get_passphrases, get_rotation_history, rotate_wlan, get_kiosk_passphrases]
+}
@@ -1,3 +1,12 @@
+use crate::model::{
+    entity::{
+        client::Client as ClientEntity, code_delivery::CodeDelivery, terms::Terms,
+        voucher_attempt::VoucherAttempt, voucher_redemption::VoucherRedemption,
+    },
+    repository::mongo_repository::MongoRepository,
+};
+use bson::doc;
+use rocket::{Build, Rocket};
 use rocket_db_pools::{Database, mongodb::Client};
 
 // Structs
@@ -5,6 +14,35 @@ use rocket_db_pools::{Database, mongodb::Client};
 #[database("mongodb")]
 pub struct MongoDb(Client);
 
+// Functions
+/// Indexes backing the queries made on every portal request and the expiry of the rate limits,
+/// built in the background so an unreachable server does not hold the launch
+pub async fn create_indexes(rocket: Rocket<Build>) -> Rocket<Build> {
+    if let Some(database) = MongoDb::fetch(&rocket).and_then(|db| db.default_database()) {
+        rocket::tokio::spawn(async move {
+            let clients = MongoRepository::<ClientEntity>::new(database.clone());
+            clients.create_index(doc! { "mac": 1, "site": 1, "start_time": -1 }, false).await;
+
+            // Two admins publishing at once must not share a version
+            let terms = MongoRepository::<Terms>::new(database.clone());
+            terms.create_index(doc! { "version": -1 }, true).await;
+
+            // Deliveries only matter inside the rate limit window
+            let deliveries = MongoRepository::<CodeDelivery>::new(database.clone());
+            deliveries.create_ttl_index("expires_at").await;
+
+            // Concurrent redemptions of a voucher must share its uses
+            let redemptions = MongoRepository::<VoucherRedemption>::new(database.clone());
+            redemptions.create_index(doc! { "site": 1, "controller": 1, "code": 1 }, true).await;
+
+            let attempts = MongoRepository::<VoucherAttempt>::new(database);
+            attempts.create_ttl_index("expires_at").await;
+        });
+    }
+
+    rocket
+}
+
 // Módulo para lidar com conversões de ObjectId <-> String
 pub mod serde_object_id {
     use bson::oid::ObjectId;
@@ -8,28 +8,37 @@ mod utils;
 mod ldap;
 mod glpi;
 
-use configurations::config::ConfigApplication;
+#[cfg(test)]
+mod tests;
+
+use configurations::config::{ConfigApplication, WlansConfig};
 use controllers::admin_controller::{self, admin_page};
 use controllers::client_controller::{self, client_connect_page, client_register};
 use controllers::error_controller::handles;
-use controllers::{approver_controller, config_controller, user_controller};
-use db::mongo_db::MongoDb;
+use controllers::{
+    approver_controller, blocklist_controller, config_controller, device_controller, session_controller, site_controller,
+    terms_controller, user_controller, voucher_controller, wlan_controller,
+};
+use db::mongo_db::{MongoDb, create_indexes};
 use glpi::glpi::GLPI;
 use ldap::ldap::LdapConnection;
+use rocket::fairing::AdHoc;
 use rocket::fs::FileServer;
 use rocket::tokio::{
     self,
-    sync::Mutex,
     time::{self, Duration},
 };
-use rocket::{Route, launch, routes};
+use rocket::{Build, Rocket, Route, launch, routes};
 use rocket_cors::{AllowedHeaders, AllowedOrigins, CorsOptions};
 use rocket_db_pools::Database;
-use rocket_db_pools::mongodb::Client;
-use std::sync::Arc;
+use rocket_db_pools::mongodb::{self, Client};
+use std::collections::HashSet;
 use tokio::sync::RwLock;
-use unifi::unifi::UnifiController;
-use utils::monitoring::{ClientsMonitoring, LdapMonitoring};
+use unifi::unifi::{UnifiController, UnifiControllers};
+use utils::client_events::ClientEvents;
+use utils::mailer::{self, Mailers};
+use utils::sms::{self, SmsGateways};
+use utils::monitoring::{ClientsMonitoring, EventsMonitoring, LdapMonitoring, WlanMonitoring};
 
 ///////////////////////////////////////////
 
@@ -38,13 +47,8 @@ async fn start() -> _ {
     // Starting Configurations...
     let config = ConfigApplication::new();
 
-    // Creating an instance of the Configuration and Request Structure to the Unifi Controller
-    let unifi = UnifiController::new(
-        config.unifi.url.clone(),
-        config.unifi.username.clone(),
-        config.unifi.password.clone(),
-    )
-    .await;
+    // Creating an instance of the Configuration and Request Structure for each Unifi Controller
+    let unifi = UnifiControllers::new(&config.unifi).await;
     
     let glpi = {
         if let Some(glpi_config) = &config.glpi { 
@@ -52,12 +56,43 @@ async fn start() -> _ {
         } else { GLPI::new("".to_string(), "".to_string(), "".to_string()) } 
     };
 
+    // Status changes of the clients, followed by the guests waiting on the portal
+    let events = ClientEvents::new();
+
+    // Gateway texting the verification codes
+    let sms = sms::gateway_for(config.clients.sms_verification.as_ref());
+
+    // Relay mailing the verification codes
+    let mailer = mailer::mailer_for(config.clients.email_verification.as_ref());
+
     // Starting scan LDAP
     tokio::spawn(monitoring_ldap(config.clone()));
 
     // Starting monitoring clients
-    tokio::spawn(monitoring_clients(unifi.clone(), config.clone()));
+    tokio::spawn(monitoring_clients(unifi.clone(), config.clone(), events.clone()));
+
+    // Starting the guest WLAN passphrase rotation
+    if let Some(wlans) = config.wlans.clone() {
+        tokio::spawn(monitoring_wlans(unifi.clone(), config.clone(), wlans));
+    }
 
+    // Starting the UniFi events subscribers, polling above stays as the reconciliation pass
+    if config.clients.events.unwrap_or(false) {
+        tokio::spawn(monitoring_events(unifi.clone(), config.clone(), events.clone()));
+    }
+
+    build_rocket(config, unifi, glpi, events, sms, mailer)
+}
+
+// Building the server with its state and routes
+fn build_rocket(
+    config: ConfigApplication,
+    unifi: UnifiControllers,
+    glpi: GLPI,
+    events: ClientEvents,
+    sms: SmsGateways,
+    mailer: Mailers,
+) -> Rocket<Build> {
     // CORS Configuration
     let allowed_origins = AllowedOrigins::all();
 
@@ -69,7 +104,7 @@ async fn start() -> _ {
             .map(|s| s.parse().unwrap())
             .collect(),
         // Permite os cabeçalhos necessários (ex: Content-Type)
-        allowed_headers: AllowedHeaders::some(&["Authorization", "Content-Type", "Accept"]),
+        allowed_headers: AllowedHeaders::some(&["Authorization", "Content-Type", "Accept", "X-Kiosk-Token"]),
         allow_credentials: true,
         ..Default::default()
     }.to_cors().unwrap();
@@ -78,8 +113,12 @@ async fn start() -> _ {
     rocket::custom(config.to_rocket_config())
         .attach(cors)
         .attach(MongoDb::init())
+        .attach(AdHoc::on_ignite("MongoDB Indexes", create_indexes))
         //
-        .manage(Arc::new( Mutex::new(unifi) ))
+        .manage(unifi)
+        .manage(events)
+        .manage(sms)
+        .manage(mailer)
         .manage(RwLock::new( glpi ))
         .manage(RwLock::new(config.clone()))
         //
@@ -98,6 +137,13 @@ fn api_routes() -> Vec<Route> {
     routes.append(&mut approver_controller::routes());
     routes.append(&mut user_controller::routes());
     routes.append(&mut config_controller::routes());
+    routes.append(&mut voucher_controller::routes());
+    routes.append(&mut site_controller::routes());
+    routes.append(&mut blocklist_controller::routes());
+    routes.append(&mut device_controller::routes());
+    routes.append(&mut session_controller::routes());
+    routes.append(&mut wlan_controller::routes());
+    routes.append(&mut terms_controller::routes());
 
     routes
 }
@@ -137,17 +183,98 @@ async fn monitoring_ldap(config: ConfigApplication) {
 
 
 // Creating monitoring that will happen in X time to align with UniFi information
-async fn monitoring_clients(unifi: UnifiController, config: ConfigApplication) {
+async fn monitoring_clients(unifi: UnifiControllers, config: ConfigApplication, events: ClientEvents) {
     let client = Client::with_uri_str(config.database.get_formated_url())
         .await
         .unwrap();
 
     let db = client.default_database().unwrap();
-    let mut monitoring = ClientsMonitoring::new(db, unifi, config.clients.clone());
+    let mut monitoring = ClientsMonitoring::new(db, unifi, config.clients.clone(), events);
 
     let mut interval = time::interval(Duration::from_secs(60));
     loop {
         interval.tick().await;
         monitoring.all().await;
+        monitoring.release_expired_blocks().await;
+    }
+}
+
+// Rotating the passphrase of the guest WLANs once it is older than the configured interval
+async fn monitoring_wlans(unifi: UnifiControllers, config: ConfigApplication, wlans: WlansConfig) {
+    let client = Client::with_uri_str(config.database.get_formated_url())
+        .await
+        .unwrap();
+
+    let db = client.default_database().unwrap();
+    let monitoring = WlanMonitoring::new(db, unifi, wlans);
+
+    let mut interval = time::interval(Duration::from_secs(300));
+    loop {
+        interval.tick().await;
+        monitoring.rotate_due().await;
+    }
+}
+
+// Subscribing to the events stream of every site to follow guests in real time, the sites are read
+// again every minute so a controller that was down or a new site gets its stream later
+async fn monitoring_events(unifi: UnifiControllers, config: ConfigApplication, events: ClientEvents) {
+    let client = Client::with_uri_str(config.database.get_formated_url())
+        .await
+        .unwrap();
+
+    let db = client.default_database().unwrap();
+    let mut subscribed: HashSet<(String, String)> = HashSet::new();
+
+    let mut interval = time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+
+        let sites = match unifi.get_sites().await {
+            Ok(sites) => sites,
+            Err(e) => {
+                println!("{e}");
+                continue;
+            }
+        };
+
+        // Every controller has its own stream, even when a site name is repeated on another one
+        for site in sites {
+            let Some(controller) = unifi.get(&site.controller) else {
+                continue;
+            };
+
+            if subscribed.insert((site.controller.clone(), site.name.clone())) {
+                tokio::spawn(subscribe_events(controller.clone(), site.name, db.clone(), events.clone()));
+            }
+        }
+    }
+}
+
+async fn subscribe_events(unifi: UnifiController, site: String, db: mongodb::Database, events: ClientEvents) {
+    let monitoring = EventsMonitoring::new(db, events);
+
+    loop {
+        match unifi.events(&site).await {
+            Ok(mut stream) => {
+                while let Some(res) = stream.next().await {
+                    match res {
+                        Ok(events) => {
+                            for event in events.iter() {
+                                monitoring.handle(unifi.name(), &site, event).await;
+                            }
+                        }
+
+                        Err(e) => {
+                            println!("{e}");
+                            break;
+                        }
+                    }
+                }
+            }
+
+            Err(e) => println!("{e}"),
+        }
+
+        time::sleep(Duration::from_secs(10)).await;
     }
 }
@@ -5,7 +5,7 @@ use super::Entity;
 
 
 // Enums
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
 pub enum ApproverGroup {
     AccessRelease,
     DirectApproval
@@ -21,7 +21,8 @@ pub struct Approver {
     pub password: String,
     pub secrete_code: String, 
     pub validity: Option<DateTime<Local>>,
-    pub group: ApproverGroup
+    pub group: ApproverGroup,
+    pub sites: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -30,7 +31,8 @@ pub struct ApproverData {
     pub email: String,
     pub password: String,
     pub secrete_code: String,
-    pub group: ApproverGroup
+    pub group: ApproverGroup,
+    pub sites: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -50,6 +52,7 @@ pub struct ApproverUpdate {
     pub email: Option<String>,
     pub password: Option<String>,
     pub secrete_code: Option<String>,
+    pub sites: Option<Vec<String>>,
 }
 
 // Impls
@@ -94,7 +97,16 @@ impl Approver {
             password: String::new(),
             secrete_code: String::new(),
             validity: None,
-            group: ApproverGroup::AccessRelease
+            group: ApproverGroup::AccessRelease,
+            sites: None,
+        }
+    }
+
+    /// Approvers without sites can approve guests on every site
+    pub fn allowed_on(&self, site: &str) -> bool {
+        match &self.sites {
+            Some(sites) => sites.iter().any(|s| s == site),
+            None => true,
         }
     }
 }
@@ -0,0 +1,69 @@
+use crate::db::mongo_db::serde_object_id;
+use chrono::{DateTime, Duration, Local};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::Entity;
+
+// Structs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockedDevice {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub mac: String,
+    pub site: String,
+    /// Controller that has the site, needed when the same site name exists on several
+    pub controller: Option<String>,
+    pub reason: String,
+    pub admin: String,
+    pub created_at: DateTime<Local>,
+    pub expires_at: Option<DateTime<Local>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockedDeviceData {
+    pub mac: String,
+    pub site: String,
+    pub controller: Option<String>,
+    pub reason: String,
+    /// Minutes until the device is released, blocked for good when missing
+    pub minutes: Option<i64>,
+}
+
+// Impls
+impl BlockedDevice {
+    pub fn new_with_data(data: &BlockedDeviceData, admin: String) -> Self {
+        let now = Local::now();
+
+        Self {
+            id: String::new(),
+            mac: data.mac.to_lowercase(),
+            site: data.site.clone(),
+            controller: data.controller.clone(),
+            reason: data.reason.clone(),
+            admin,
+            created_at: now,
+            expires_at: data.minutes.map(|m| now + Duration::minutes(m)),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(date) => Local::now() > date,
+            None => false,
+        }
+    }
+}
+
+impl Entity<String> for BlockedDevice {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("Blocklist")
+    }
+}
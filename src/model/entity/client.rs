@@ -1,10 +1,10 @@
 use std::collections::HashMap;
 
-use crate::{configurations::config::ClientsConfig, db::mongo_db::serde_object_id, utils::validator::Validator};
-use chrono::{DateTime, Local};
+use crate::{db::mongo_db::serde_object_id, utils::validator::Validator};
+use chrono::{DateTime, Duration, Local};
 use rocket::serde::{Deserialize, Serialize};
 
-use super::Entity;
+use super::{Entity, portal_form::PortalForm, terms::TermsAcceptance};
 
 // Enums
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -15,6 +15,13 @@ pub enum ClientStatus {
     Expired,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum ClientChangeKind {
+    Extended,
+    Shortened,
+    Ended,
+}
+
 // Structs
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ClientData {
@@ -22,7 +29,34 @@ pub struct ClientData {
     pub email: String,
     pub phone: String,
     pub approver_code: Option<String>,
-    pub fields: HashMap<String, String>
+    pub fields: HashMap<String, String>,
+    /// Version of the terms of use the guest accepted
+    pub terms_version: Option<u32>,
+    /// One-time code received by the guest, when the verification is enabled
+    pub verification_code: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClientChange {
+    pub kind: ClientChangeKind,
+    pub minutes: i64,
+    pub by: String,
+    pub at: DateTime<Local>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClientTimeUpdate {
+    /// Minutes added to the session, negative values shorten it
+    pub minutes: i64,
+    pub approver_code: Option<String>,
+}
+
+/// Limits sent with `authorize-guest`: rates in Kbps, data cap in MB
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ClientLimits {
+    pub up: Option<u32>,
+    pub down: Option<u32>,
+    pub bytes: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -31,8 +65,11 @@ pub struct ClientInfo {
     pub data: Option<ClientData>,
     pub mac: String,
     pub site: String,
+    /// Controller that has the site, needed when the same site name exists on several
+    pub controller: Option<String>,
     pub minutes: u16,
     pub connect: bool,
+    pub limits: Option<ClientLimits>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -47,15 +84,26 @@ pub struct Client {
 
     pub mac: String,
     pub site: String,
+    /// Name of the configured controller the guest was found on
+    pub controller: Option<String>,
     pub status: ClientStatus,
+    /// MAC of the access point the guest connected through
+    pub ap: Option<String>,
 
     pub hostname: Option<String>,
     pub tx_bytes: Option<usize>,
     pub rx_bytes: Option<usize>,
+    pub connected: Option<bool>,
+    pub last_seen: Option<DateTime<Local>>,
 
     pub time_connection: String,
     pub start_time: DateTime<Local>,
     pub approver: String,
+    pub limits: Option<ClientLimits>,
+    /// Name of the UniFi user group the device is moved to
+    pub user_group: Option<String>,
+    pub terms: Option<TermsAcceptance>,
+    pub changes: Option<Vec<ClientChange>>,
 }
 
 // Impls
@@ -71,14 +119,22 @@ impl Client {
 
             mac: String::from("---"),
             site: String::from("---"),
+            controller: None,
             
             status: ClientStatus::Pending,
+            ap: None,
             hostname: None,
             tx_bytes: None,
             rx_bytes: None,
+            connected: None,
+            last_seen: None,
             time_connection: String::from("0"),
             start_time: Local::now(),
             approver: String::from("---"),
+            limits: None,
+            user_group: None,
+            terms: None,
+            changes: None,
         }
     }
 
@@ -92,14 +148,22 @@ impl Client {
 
             mac: String::from("---"),
             site: String::from("---"),
+            controller: None,
             status: ClientStatus::Pending,
+            ap: None,
 
             hostname: None,
             tx_bytes: None,
             rx_bytes: None,
+            connected: None,
+            last_seen: None,
             time_connection: String::from("0"),
             start_time: Local::now(),
-            approver: String::from("---") 
+            approver: String::from("---"),
+            limits: None,
+            user_group: None,
+            terms: None,
+            changes: None,
         }
     }
 
@@ -113,18 +177,26 @@ impl Client {
 
             mac: info.mac.clone(),
             site: info.site.clone(),
+            controller: info.controller.clone(),
             
             status: if info.connect {
                 ClientStatus::Approved
             } else {
                 ClientStatus::Reject
             },
+            ap: None,
             hostname: None,
             tx_bytes: None,
             rx_bytes: None,
+            connected: None,
+            last_seen: None,
             time_connection: format!("{}", info.minutes.clone()),
             start_time: Local::now(),
             approver: String::from("---"),
+            limits: info.limits.clone(),
+            user_group: None,
+            terms: None,
+            changes: None,
         };
 
         if let Some(data) = info.data.clone() {
@@ -136,12 +208,48 @@ impl Client {
 
         client
     }
-    
+
+    pub fn end_time(&self) -> DateTime<Local> {
+        let minutes = self.time_connection.parse::<i64>().unwrap_or(0);
+        self.start_time + Duration::minutes(minutes)
+    }
+
+    pub fn record_change(&mut self, kind: ClientChangeKind, minutes: i64, by: String) {
+        let change = ClientChange {
+            kind,
+            minutes,
+            by,
+            at: Local::now(),
+        };
+
+        self.changes.get_or_insert_with(Vec::new).push(change);
+    }
+}
+
+impl ClientLimits {
+    /// Returns these limits with every value set in `other` taking precedence
+    pub fn merge(&self, other: &ClientLimits) -> ClientLimits {
+        ClientLimits {
+            up: other.up.or(self.up),
+            down: other.down.or(self.down),
+            bytes: other.bytes.or(self.bytes),
+        }
+    }
 }
 
 impl ClientData {
-    pub fn validate_form(&self, config: ClientsConfig) -> bool {
-        Validator::validate_client(&config, self)  
+    pub fn validate_form(&self, form: &PortalForm) -> Result<(), HashMap<String, String>> {
+        Validator::validate_client(form, self)
+    }
+
+    /// Value sent for a form field, from the record fields or the free ones
+    pub fn value_of(&self, name: &str) -> Option<&str> {
+        match name {
+            "full_name" => Some(&self.full_name),
+            "email" => Some(&self.email),
+            "phone" => Some(&self.phone),
+            _ => self.fields.get(name).map(String::as_str),
+        }
     }
 }
 
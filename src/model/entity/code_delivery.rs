@@ -0,0 +1,54 @@
+use crate::db::mongo_db::serde_object_id;
+use bson::DateTime;
+use chrono::{Duration, Local};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::{Entity, verification::VerificationChannel};
+
+// Structs
+/// One verification code sent to a guest, counted by the rate limits of its channel
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CodeDelivery {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub channel: VerificationChannel,
+    pub mac: String,
+    pub site: String,
+    /// Email address or phone number the code was sent to
+    pub target: String,
+    /// Kept as BSON dates, so the window is filtered by the database
+    pub sent_at: DateTime,
+    /// End of the rate limit window, the TTL index removes the delivery after it
+    pub expires_at: DateTime,
+}
+
+// Impls
+impl CodeDelivery {
+    pub fn new(channel: VerificationChannel, mac: &str, site: &str, target: &str, window_minutes: i64) -> Self {
+        let now = Local::now();
+
+        Self {
+            id: String::new(),
+            channel,
+            mac: mac.to_lowercase(),
+            site: site.to_string(),
+            target: target.to_lowercase(),
+            sent_at: DateTime::from_millis(now.timestamp_millis()),
+            expires_at: DateTime::from_millis((now + Duration::minutes(window_minutes)).timestamp_millis()),
+        }
+    }
+}
+
+impl Entity<String> for CodeDelivery {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("CodeDeliveries")
+    }
+}
@@ -0,0 +1,163 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::unifi::unifi::GuestAccessSetting;
+use regex::Regex;
+use rocket::serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+const ALLOWED_PREFIX: &str = "allowed_subnet_";
+const HOSTNAME_PATTERN: &str = r"^(\*\.)?([a-zA-Z0-9]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?\.)+[a-zA-Z]{2,63}$";
+
+// Enums
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GuestAuth {
+    /// Open network, no portal
+    None,
+    /// Portal hosted by the controller
+    Hotspot,
+    /// External portal server, this application
+    Custom,
+}
+
+// Structs
+/// Guest portal settings of a site, typed view of the UniFi `guest_access` setting
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GuestPortal {
+    pub auth: GuestAuth,
+    /// IPv4 of the external portal server, required with the custom auth
+    pub portal_ip: Option<String>,
+    /// Hostname shown to guests instead of the controller address
+    pub portal_hostname: Option<String>,
+    /// Page opened after the authorization, the originally requested URL when missing
+    pub redirect_url: Option<String>,
+    pub redirect_https: bool,
+    /// Subnets reachable before the authorization
+    pub allowed_subnets: Vec<String>,
+    /// Hosts reachable before the authorization (walled garden)
+    pub allowed_hosts: Vec<String>,
+}
+
+// Impls
+impl GuestPortal {
+    pub fn new_with_setting(setting: &GuestAccessSetting) -> Self {
+        let values = &setting.values;
+        let text = |key: &str| {
+            values
+                .get(key)
+                .and_then(|v| v.as_str())
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+        };
+        let flag = |key: &str| values.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let auth = match text("auth").as_deref() {
+            Some("custom") => GuestAuth::Custom,
+            Some("none") => GuestAuth::None,
+            _ => GuestAuth::Hotspot,
+        };
+
+        // UniFi keeps the pre-authorization list in numbered keys, subnets and hostnames alike
+        let mut allowed: Vec<(usize, String)> = values
+            .iter()
+            .filter_map(|(k, v)| {
+                let index = k.strip_prefix(ALLOWED_PREFIX)?.parse::<usize>().ok()?;
+                let value = v.as_str().filter(|v| !v.is_empty())?;
+                Some((index, value.to_string()))
+            })
+            .collect();
+        allowed.sort();
+
+        let (allowed_subnets, allowed_hosts) = allowed
+            .into_iter()
+            .map(|(_, v)| v)
+            .partition(|v| is_subnet(v));
+
+        Self {
+            auth,
+            portal_ip: text("custom_ip"),
+            portal_hostname: text("portal_hostname").filter(|_| flag("portal_use_hostname")),
+            redirect_url: text("redirect_url").filter(|_| flag("redirect_enabled")),
+            redirect_https: flag("redirect_https"),
+            allowed_subnets,
+            allowed_hosts,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.portal_ip {
+            Some(ip) if ip.parse::<Ipv4Addr>().is_err() => return Err(format!("Invalid portal_ip: {ip}")),
+            None if self.auth == GuestAuth::Custom => return Err(String::from("portal_ip is required with the custom auth")),
+            _ => {}
+        }
+
+        let hostname = Regex::new(HOSTNAME_PATTERN).unwrap();
+
+        if let Some(host) = &self.portal_hostname
+            && !hostname.is_match(host)
+        {
+            return Err(format!("Invalid portal_hostname: {host}"));
+        }
+
+        if let Some(url) = &self.redirect_url {
+            let valid = reqwest::Url::parse(url)
+                .map(|u| u.scheme() == "http" || u.scheme() == "https")
+                .unwrap_or(false);
+
+            if !valid {
+                return Err(format!("Invalid redirect_url: {url}"));
+            }
+        }
+
+        if let Some(subnet) = self.allowed_subnets.iter().find(|s| !is_subnet(s)) {
+            return Err(format!("Invalid subnet: {subnet}"));
+        }
+
+        if let Some(host) = self.allowed_hosts.iter().find(|h| !hostname.is_match(h)) {
+            return Err(format!("Invalid host: {host}"));
+        }
+
+        Ok(())
+    }
+
+    /// Writes these settings over the UniFi document, keeping the keys not managed here
+    pub fn apply(&self, setting: &mut GuestAccessSetting) {
+        let values = &mut setting.values;
+        let auth = match self.auth {
+            GuestAuth::None => "none",
+            GuestAuth::Hotspot => "hotspot",
+            GuestAuth::Custom => "custom",
+        };
+
+        values.insert("auth".into(), json!(auth));
+        values.insert("custom_ip".into(), json!(self.portal_ip.clone().unwrap_or_default()));
+        values.insert("portal_use_hostname".into(), json!(self.portal_hostname.is_some()));
+        values.insert("portal_hostname".into(), json!(self.portal_hostname.clone().unwrap_or_default()));
+        values.insert("redirect_enabled".into(), json!(self.redirect_url.is_some()));
+        values.insert("redirect_url".into(), json!(self.redirect_url.clone().unwrap_or_default()));
+        values.insert("redirect_https".into(), json!(self.redirect_https));
+
+        // Unused slots are emptied, the controller keeps the keys missing from the update
+        for (key, value) in values.iter_mut() {
+            if key.starts_with(ALLOWED_PREFIX) {
+                *value = Value::String(String::new());
+            }
+        }
+
+        let allowed = self.allowed_subnets.iter().chain(self.allowed_hosts.iter());
+        for (i, value) in allowed.enumerate() {
+            values.insert(format!("{ALLOWED_PREFIX}{}", i + 1), json!(value));
+        }
+    }
+}
+
+// Functions
+fn is_subnet(value: &str) -> bool {
+    let (ip, prefix) = value.split_once('/').unwrap_or((value, "32"));
+
+    match (ip.parse::<IpAddr>(), prefix.parse::<u8>()) {
+        (Ok(IpAddr::V4(_)), Ok(p)) => p <= 32,
+        (Ok(IpAddr::V6(_)), Ok(p)) => p <= 128,
+        _ => false,
+    }
+}
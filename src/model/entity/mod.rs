@@ -1,7 +1,20 @@
 pub mod admin;
 pub mod approver;
+pub mod blocked_device;
 pub mod client;
+pub mod code_delivery;
+pub mod guest_portal;
+pub mod portal_form;
+pub mod psk_rotation;
+pub mod reconciliation;
+pub mod session;
+pub mod site;
+pub mod terms;
 pub mod user;
+pub mod verification;
+pub mod voucher;
+pub mod voucher_attempt;
+pub mod voucher_redemption;
 
 // Traits
 pub trait Entity<I> {
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use crate::configurations::config::ClientInfo;
+use rocket::serde::{Deserialize, Serialize};
+
+// Enums
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    Text,
+    Email,
+    Phone,
+    Select,
+    /// Sent as "true" or "false", required means it has to be checked
+    Checkbox,
+    /// Sent as YYYY-MM-DD
+    Date,
+}
+
+// Structs
+/// Fields the guests fill on the portal, the first form matching the site and SSID is used
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PortalForm {
+    /// Any site when missing
+    pub site: Option<String>,
+    /// Any SSID when missing
+    pub ssid: Option<String>,
+    pub fields: Vec<FormField>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FormField {
+    /// `full_name`, `email` and `phone` fill the client record, any other name goes to its fields
+    pub name: String,
+    /// Label by language code, e.g. "en" or "pt-BR"
+    pub label: HashMap<String, String>,
+    #[serde(rename = "type")]
+    pub kind: FieldType,
+    pub required: bool,
+    /// Values accepted by a select
+    pub options: Option<Vec<FieldOption>>,
+    pub regex: Option<String>,
+    /// Shown instead of the default message when the value is refused
+    pub error_message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FieldOption {
+    pub value: String,
+    pub label: HashMap<String, String>,
+}
+
+// Impls
+impl PortalForm {
+    pub fn matches(&self, site: &str, ssid: Option<&str>) -> bool {
+        let site_matches = self.site.as_deref().is_none_or(|s| s == site);
+        let ssid_matches = self.ssid.is_none() || self.ssid.as_deref() == ssid;
+
+        site_matches && ssid_matches
+    }
+
+    /// Schema equivalent to the regex list of the `info` setting, kept for the configurations written before the forms
+    pub fn new_with_info(info: &ClientInfo) -> Self {
+        let base = [
+            ("full_name", &info.name_validated),
+            ("email", &info.email_validated),
+            ("phone", &info.phone_validated),
+        ];
+
+        let base = base
+            .into_iter()
+            .filter_map(|(name, regex)| Some((name.to_string(), regex.clone()?)));
+
+        let fields = base
+            .chain(info.fields.iter().cloned())
+            .map(|(name, regex)| FormField {
+                label: HashMap::from([(String::from("en"), name.clone())]),
+                name,
+                kind: FieldType::Text,
+                required: true,
+                options: None,
+                regex: Some(regex),
+                error_message: None,
+            })
+            .collect();
+
+        Self {
+            site: None,
+            ssid: None,
+            fields,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::db::mongo_db::serde_object_id;
+use chrono::{DateTime, Local};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::Entity;
+
+// Structs
+/// Passphrase set on a guest WLAN, the latest one of each SSID is the current passphrase
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PskRotation {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub site: String,
+    pub ssid: String,
+    pub passphrase: String,
+    pub rotated_at: DateTime<Local>,
+    /// "schedule", "controller" for the passphrase found on the first run, or the admin name
+    pub by: String,
+}
+
+// Impls
+impl PskRotation {
+    pub fn new(site: String, ssid: String, passphrase: String, by: String) -> Self {
+        Self {
+            id: String::new(),
+            site,
+            ssid,
+            passphrase,
+            rotated_at: Local::now(),
+            by,
+        }
+    }
+}
+
+impl Entity<String> for PskRotation {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("PskRotations")
+    }
+}
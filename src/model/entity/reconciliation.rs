@@ -0,0 +1,108 @@
+use crate::{db::mongo_db::serde_object_id, unifi::unifi::DeviceInfo};
+use chrono::{DateTime, Local, TimeZone};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::{
+    Entity,
+    client::{Client, ClientStatus},
+};
+
+// Structs
+/// Differences between the guests authorized on a site and our client records, one per site
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Reconciliation {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub site: String,
+    /// Controller that has the site, sites with the same name on several controllers get one report each
+    pub controller: Option<String>,
+    pub checked_at: DateTime<Local>,
+    /// Guests authorized on the controller without an approved record
+    pub unknown_guests: Vec<UnknownGuest>,
+    /// Approved records the controller reports as unauthorized or no longer lists
+    pub unauthorized_clients: Vec<UnauthorizedClient>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnknownGuest {
+    pub mac: String,
+    pub hostname: Option<String>,
+    pub ap: Option<String>,
+    pub voucher_code: Option<String>,
+    pub end: Option<DateTime<Local>>,
+    /// True when the unauthorize policy already removed its access
+    pub unauthorized: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnauthorizedClient {
+    pub client_id: String,
+    pub mac: String,
+    pub approver: String,
+    /// True when the device is not listed by the controller at all
+    pub missing: bool,
+}
+
+// Impls
+impl Reconciliation {
+    pub fn new_with_devices(site: String, clients: &[Client], devices: &[DeviceInfo]) -> Self {
+        let approved: Vec<&Client> = clients
+            .iter()
+            .filter(|c| c.status == ClientStatus::Approved)
+            .collect();
+
+        let unknown_guests = devices
+            .iter()
+            .filter(|d| d.expired == Some(false))
+            .filter(|d| !approved.iter().any(|c| c.mac == d.mac))
+            .map(|d| UnknownGuest {
+                mac: d.mac.clone(),
+                hostname: d.hostname.clone(),
+                ap: d.ap_mac.clone(),
+                voucher_code: d.voucher_code.clone(),
+                end: d.end.and_then(|t| Local.timestamp_opt(t as i64, 0).single()),
+                unauthorized: false,
+            })
+            .collect();
+
+        let unauthorized_clients = approved
+            .iter()
+            .filter_map(|c| {
+                let device = devices.iter().find(|d| d.mac == c.mac);
+                if device.is_some_and(|d| d.expired == Some(false)) {
+                    return None;
+                }
+
+                Some(UnauthorizedClient {
+                    client_id: c.id.clone(),
+                    mac: c.mac.clone(),
+                    approver: c.approver.clone(),
+                    missing: device.is_none(),
+                })
+            })
+            .collect();
+
+        Self {
+            id: String::new(),
+            site,
+            controller: None,
+            checked_at: Local::now(),
+            unknown_guests,
+            unauthorized_clients,
+        }
+    }
+}
+
+impl Entity<String> for Reconciliation {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("Reconciliations")
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use crate::{db::mongo_db::serde_object_id, unifi::unifi::DeviceInfo};
+use chrono::{DateTime, Local, TimeZone};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::{Entity, client::Client};
+
+// Structs
+/// One association of a guest device, from the snapshots taken by the monitoring
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub client_id: String,
+    pub mac: String,
+    pub site: String,
+    /// Controller of the guest, missing on the records written before it was kept
+    pub controller: Option<String>,
+    pub ap: Option<String>,
+    pub ip: Option<String>,
+    pub ssid: Option<String>,
+    pub start: DateTime<Local>,
+    /// Missing while the device is still connected
+    pub end: Option<DateTime<Local>>,
+    pub last_update: DateTime<Local>,
+    pub tx_bytes: usize,
+    pub rx_bytes: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SessionTraffic {
+    /// Day the sessions started on (YYYY-MM-DD)
+    pub day: String,
+    pub sessions: usize,
+    pub tx_bytes: usize,
+    pub rx_bytes: usize,
+}
+
+// Impls
+impl Session {
+    pub fn new_with_device(client: &Client, device: &DeviceInfo) -> Self {
+        let now = Local::now();
+
+        let mut session = Self {
+            id: String::new(),
+            client_id: client.id.clone(),
+            mac: device.mac.clone(),
+            site: client.site.clone(),
+            controller: client.controller.clone(),
+            ap: None,
+            ip: None,
+            ssid: None,
+            start: Self::association_time(device).unwrap_or(now),
+            end: None,
+            last_update: now,
+            tx_bytes: 0,
+            rx_bytes: 0,
+        };
+
+        session.update_with_device(device);
+        session
+    }
+
+    pub fn association_time(device: &DeviceInfo) -> Option<DateTime<Local>> {
+        device
+            .assoc_time
+            .and_then(|t| Local.timestamp_opt(t as i64, 0).single())
+    }
+
+    /// The counters of the controller are per association, so the latest snapshot is the session total
+    pub fn update_with_device(&mut self, device: &DeviceInfo) {
+        self.last_update = Local::now();
+
+        if device.ap_mac.is_some() {
+            self.ap = device.ap_mac.clone();
+        }
+
+        if device.ip.is_some() {
+            self.ip = device.ip.clone();
+        }
+
+        if device.essid.is_some() {
+            self.ssid = device.essid.clone();
+        }
+
+        self.tx_bytes = device.tx_bytes.unwrap_or(self.tx_bytes);
+        self.rx_bytes = device.rx_bytes.unwrap_or(self.rx_bytes);
+    }
+
+    pub fn close(&mut self) {
+        self.end = Some(self.last_update);
+    }
+
+    pub fn is_same_association(&self, device: &DeviceInfo) -> bool {
+        match Self::association_time(device) {
+            Some(start) => start == self.start,
+            None => true,
+        }
+    }
+}
+
+impl SessionTraffic {
+    /// Totals per day, newest first. A session counts on the day it started
+    pub fn new_with_sessions(sessions: &[Session]) -> Vec<Self> {
+        let mut days: BTreeMap<String, SessionTraffic> = BTreeMap::new();
+
+        for s in sessions {
+            let day = s.start.format("%Y-%m-%d").to_string();
+            let traffic = days.entry(day.clone()).or_insert_with(|| SessionTraffic {
+                day,
+                ..Default::default()
+            });
+
+            traffic.sessions += 1;
+            traffic.tx_bytes += s.tx_bytes;
+            traffic.rx_bytes += s.rx_bytes;
+        }
+
+        days.into_values().rev().collect()
+    }
+}
+
+impl Entity<String> for Session {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("Sessions")
+    }
+}
@@ -0,0 +1,32 @@
+use rocket::serde::{Deserialize, Serialize};
+
+use super::client::{Client, ClientStatus};
+
+// Structs
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SiteStats {
+    pub site: String,
+    pub clients: usize,
+    pub approved: usize,
+    pub pending: usize,
+    pub rejected: usize,
+    pub expired: usize,
+    pub guests_connected: usize,
+}
+
+// Impls
+impl SiteStats {
+    pub fn new_with_clients(site: String, clients: &[Client]) -> Self {
+        let count = |status: ClientStatus| clients.iter().filter(|c| c.status == status).count();
+
+        Self {
+            site,
+            clients: clients.len(),
+            approved: count(ClientStatus::Approved),
+            pending: count(ClientStatus::Pending),
+            rejected: count(ClientStatus::Reject),
+            expired: count(ClientStatus::Expired),
+            guests_connected: 0,
+        }
+    }
+}
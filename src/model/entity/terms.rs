@@ -0,0 +1,74 @@
+use std::net::IpAddr;
+
+use crate::db::mongo_db::serde_object_id;
+use chrono::{DateTime, Local};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::Entity;
+
+// Structs
+/// One published version of the terms of use, versions are never edited so the accepted text can be proven
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Terms {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub version: u32,
+    pub title: String,
+    pub content: String,
+    pub published_at: DateTime<Local>,
+    pub admin: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TermsData {
+    pub title: String,
+    pub content: String,
+}
+
+/// Proof of consent kept with the client record
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TermsAcceptance {
+    pub version: u32,
+    pub accepted_at: DateTime<Local>,
+    pub ip: Option<String>,
+    pub mac: String,
+}
+
+// Impls
+impl Terms {
+    pub fn new_with_data(data: &TermsData, version: u32, admin: String) -> Self {
+        Self {
+            id: String::new(),
+            version,
+            title: data.title.clone(),
+            content: data.content.clone(),
+            published_at: Local::now(),
+            admin,
+        }
+    }
+}
+
+impl TermsAcceptance {
+    pub fn new(version: u32, mac: &str, ip: Option<IpAddr>) -> Self {
+        Self {
+            version,
+            accepted_at: Local::now(),
+            ip: ip.map(|ip| ip.to_string()),
+            mac: mac.to_lowercase(),
+        }
+    }
+}
+
+impl Entity<String> for Terms {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("Terms")
+    }
+}
@@ -19,6 +19,8 @@ pub struct User {
 pub struct UserLogin {
     pub username: String,
     pub password: String,
+    /// Version of the terms of use the guest accepted
+    pub terms_version: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -50,7 +52,9 @@ impl User {
             email: ldap_user.email.clone(),
             phone: String::new(),
             fields: HashMap::new(),
-            approver_code: None
+            approver_code: None,
+            terms_version: None,
+            verification_code: None,
         };
 
         Self {
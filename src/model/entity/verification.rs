@@ -0,0 +1,66 @@
+use crate::db::mongo_db::serde_object_id;
+use chrono::{DateTime, Duration, Local};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::Entity;
+
+// Enums
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum VerificationChannel {
+    Email,
+    Sms,
+}
+
+// Structs
+/// One-time code sent to a guest, the code is kept hashed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Verification {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub channel: VerificationChannel,
+    pub mac: String,
+    pub site: String,
+    /// Address the code was sent to
+    pub target: String,
+    pub code: String,
+    pub attempts: u8,
+    pub created_at: DateTime<Local>,
+    pub expires_at: DateTime<Local>,
+}
+
+// Impls
+impl Verification {
+    pub fn new(channel: VerificationChannel, mac: &str, site: &str, target: &str, code: String, minutes: u16) -> Self {
+        let now = Local::now();
+
+        Self {
+            id: String::new(),
+            channel,
+            mac: mac.to_lowercase(),
+            site: site.to_string(),
+            target: target.to_lowercase(),
+            code,
+            attempts: 0,
+            created_at: now,
+            expires_at: now + Duration::minutes(minutes as i64),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Local::now() > self.expires_at
+    }
+}
+
+impl Entity<String> for Verification {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("Verifications")
+    }
+}
@@ -0,0 +1,30 @@
+use rocket::serde::{Deserialize, Serialize};
+
+use super::client::{ClientData, ClientLimits};
+
+// Structs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoucherData {
+    pub site: String,
+    pub count: u32,
+    pub minutes: u32,
+    pub quota: u32,
+    pub note: Option<String>,
+    pub limits: Option<ClientLimits>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoucherRedeem {
+    pub code: String,
+    pub data: Option<ClientData>,
+    /// Version of the terms of use the guest accepted
+    pub terms_version: Option<u32>,
+}
+
+// Impls
+impl VoucherRedeem {
+    /// Codes are printed as `12345-67890` but stored by the controller without the dash
+    pub fn normalized_code(&self) -> String {
+        self.code.chars().filter(|c| c.is_ascii_digit()).collect()
+    }
+}
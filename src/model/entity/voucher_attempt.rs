@@ -0,0 +1,46 @@
+use crate::db::mongo_db::serde_object_id;
+use bson::DateTime;
+use chrono::{Duration, Local};
+use rocket::serde::{Deserialize, Serialize};
+
+use super::Entity;
+
+// Structs
+/// Voucher code refused for a device, counted to stop the guessing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoucherAttempt {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub mac: String,
+    pub site: String,
+    /// The TTL index removes the attempt after it
+    pub expires_at: DateTime,
+}
+
+// Impls
+impl VoucherAttempt {
+    pub fn new(mac: &str, site: &str, window_minutes: i64) -> Self {
+        let expires_at = Local::now() + Duration::minutes(window_minutes);
+
+        Self {
+            id: String::new(),
+            mac: mac.to_lowercase(),
+            site: site.to_string(),
+            expires_at: DateTime::from_millis(expires_at.timestamp_millis()),
+        }
+    }
+}
+
+impl Entity<String> for VoucherAttempt {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("VoucherAttempts")
+    }
+}
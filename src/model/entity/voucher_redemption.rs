@@ -0,0 +1,32 @@
+use crate::db::mongo_db::serde_object_id;
+use rocket::serde::{Deserialize, Serialize};
+
+use super::Entity;
+
+// Structs
+/// Uses of a voucher made through the portal, unique by site and code so concurrent redemptions
+/// reserve them one at a time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoucherRedemption {
+    #[serde(rename = "_id", with = "serde_object_id")]
+    pub id: String,
+    pub site: String,
+    pub controller: Option<String>,
+    pub code: String,
+    pub uses: u32,
+}
+
+// Impls
+impl Entity<String> for VoucherRedemption {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn set_id(&mut self, new_id: String) {
+        self.id = new_id;
+    }
+
+    fn get_name() -> String {
+        String::from("VoucherRedemptions")
+    }
+}
@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, time::Duration};
 
 use super::Repository;
 use crate::{db::mongo_db::MongoDb, model::entity::Entity};
@@ -8,7 +8,13 @@ use rocket::{
     request::{FromRequest, Outcome, Request},
     serde::{Serialize, DeserializeOwned}
 };
-use rocket_db_pools::{Connection, mongodb::Database};
+use rocket_db_pools::{
+    Connection,
+    mongodb::{
+        Database, IndexModel,
+        options::{FindOneAndUpdateOptions, FindOneOptions, IndexOptions, ReturnDocument},
+    },
+};
 
 // Structs
 pub struct MongoRepository<E> {
@@ -24,6 +30,53 @@ impl<E: Entity<String> + Serialize  + DeserializeOwned + Unpin + Send + Sync> Mo
             _phantom: PhantomData
         }
     }
+
+    /// First document of the query in the given order, pair it with an index on the same keys
+    pub async fn find_first(&self, query: Document, sort: Document) -> Option<E> {
+        let collection = self.database.collection::<E>(&E::get_name());
+        let options = FindOneOptions::builder().sort(sort).build();
+
+        collection.find_one(query, options).await.unwrap_or_default()
+    }
+
+    /// Applies `modify` to the document of the query, inserting it when missing, and returns it
+    /// changed. Pair it with a unique index on the query keys, a concurrent insert then fails
+    pub async fn upsert_one(&self, query: Document, modify: Document) -> Option<E> {
+        let collection = self.database.collection::<E>(&E::get_name());
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match collection.find_one_and_update(query, modify, options).await {
+            Ok(entity) => entity,
+            Err(e) => {
+                println!("{e}");
+                None
+            }
+        }
+    }
+
+    pub async fn create_index(&self, keys: Document, unique: bool) {
+        let collection = self.database.collection::<E>(&E::get_name());
+        let options = IndexOptions::builder().unique(unique).build();
+        let index = IndexModel::builder().keys(keys).options(options).build();
+
+        if let Err(e) = collection.create_index(index, None).await {
+            println!("{e}");
+        }
+    }
+
+    /// Lets the server remove each document once the date in `field` has passed
+    pub async fn create_ttl_index(&self, field: &str) {
+        let collection = self.database.collection::<E>(&E::get_name());
+        let options = IndexOptions::builder().expire_after(Duration::ZERO).build();
+        let index = IndexModel::builder().keys(doc! { field: 1 }).options(options).build();
+
+        if let Err(e) = collection.create_index(index, None).await {
+            println!("{e}");
+        }
+    }
 }
 
 impl<E: Entity<String> + Serialize  + DeserializeOwned + Unpin + Send + Sync> Repository for MongoRepository<E> {
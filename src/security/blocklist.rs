@@ -0,0 +1,8 @@
+use crate::model::{entity::blocked_device::BlockedDevice, repository::{mongo_repository::MongoRepository, Repository}};
+use bson::doc;
+
+// Functions
+pub async fn is_blocked(mac: &str, repository: &MongoRepository<BlockedDevice>) -> bool {
+    let entries = repository.find(doc! { "mac": mac.to_lowercase() }).await;
+    entries.iter().any(|b| !b.is_expired())
+}
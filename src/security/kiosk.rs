@@ -0,0 +1,32 @@
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+};
+use subtle::ConstantTimeEq;
+
+const HEADER: &str = "X-Kiosk-Token";
+
+// Structs
+/// Token sent by the reception kiosk in the `X-Kiosk-Token` header
+pub struct KioskToken(String);
+
+// Impls
+impl KioskToken {
+    /// Compared in constant time, so the response time does not tell how much of the token is right
+    pub fn matches(&self, expected: &str) -> bool {
+        self.0.as_bytes().ct_eq(expected.as_bytes()).into()
+    }
+}
+
+// Guards
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for KioskToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.headers().get_one(HEADER) {
+            Some(token) => Outcome::Success(KioskToken(token.to_string())),
+            None => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
@@ -1,2 +1,6 @@
 pub mod approval_code;
 pub mod auth_jwt;
+pub mod blocklist;
+pub mod kiosk;
+pub mod portal_session;
+pub mod verification;
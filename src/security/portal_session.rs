@@ -0,0 +1,99 @@
+use crate::configurations::config::ConfigApp;
+use chrono::Local;
+use rocket::{
+    State,
+    http::{Cookie, CookieJar, SameSite, Status},
+    request::{FromRequest, Outcome, Request},
+    serde::{Deserialize, Serialize},
+};
+
+const COOKIE: &str = "portal";
+pub const DEFAULT_MINUTES: i64 = 30;
+/// Seconds the clock of the controller may be ahead of ours
+const MAX_CLOCK_SKEW: i64 = 60;
+
+// Structs
+/// Redirect parameters sent by UniFi, kept in an encrypted cookie so the guest cannot change them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PortalSession {
+    pub mac: String,
+    pub site: String,
+    /// Name of the configured controller the device was found associated on
+    pub controller: Option<String>,
+    pub ap: Option<String>,
+    pub ssid: Option<String>,
+    pub url: Option<String>,
+    /// Unix time the session was issued, taken from our clock and not from the redirect
+    pub issued_at: i64,
+}
+
+/// Reason the portal session was refused, answered by the 400 catcher
+pub struct PortalSessionError(pub &'static str);
+
+// Impls
+impl PortalSession {
+    pub fn new(mac: String, site: String, ap: Option<String>, ssid: Option<String>, url: Option<String>) -> Self {
+        Self {
+            mac: mac.to_lowercase(),
+            site,
+            controller: None,
+            ap: ap.map(|a| a.to_lowercase()),
+            ssid,
+            url,
+            issued_at: Local::now().timestamp(),
+        }
+    }
+
+    pub fn is_expired(&self, minutes: i64) -> bool {
+        Local::now().timestamp() - self.issued_at > minutes * 60
+    }
+
+    /// The `t` of the UniFi redirect can be replayed or forged, so only a recent one is accepted
+    pub fn is_recent_redirect(t: i64, minutes: i64) -> bool {
+        let now = Local::now().timestamp();
+        t >= now - minutes * 60 && t <= now + MAX_CLOCK_SKEW
+    }
+
+    pub fn save(&self, cookies: &CookieJar<'_>) {
+        cookies.add_private(self.to_cookie());
+    }
+
+    pub fn to_cookie(&self) -> Cookie<'static> {
+        Cookie::build((COOKIE, serde_json::to_string(self).unwrap()))
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .build()
+    }
+}
+
+// Guards
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PortalSession {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = request
+            .guard::<&State<ConfigApp>>()
+            .await
+            .unwrap()
+            .read()
+            .await;
+
+        let minutes = config.clients.portal_session_minutes.unwrap_or(DEFAULT_MINUTES);
+        let session = request
+            .cookies()
+            .get_private(COOKIE)
+            .and_then(|c| serde_json::from_str::<PortalSession>(c.value()).ok());
+
+        let refuse = |msg: &'static str| {
+            request.local_cache(|| Some(PortalSessionError(msg)));
+            Outcome::Error((Status::BadRequest, ()))
+        };
+
+        match session {
+            None => refuse("Portal session not found, reconnect to the Wi-Fi network"),
+            Some(s) if s.is_expired(minutes) => refuse("Portal session expired, reconnect to the Wi-Fi network"),
+            Some(s) => Outcome::Success(s),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    configurations::config::{EmailVerificationConfig, SmsVerificationConfig},
+    model::{
+        entity::{
+            code_delivery::CodeDelivery,
+            verification::{Verification, VerificationChannel},
+        },
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    utils::{
+        error::{BadRequest, CustomError, Error},
+        generator::generator_code,
+        mailer::Mailer,
+        sms::SmsGateway,
+    },
+};
+use bcrypt::{DEFAULT_COST, hash, verify};
+use bson::{DateTime, doc};
+use chrono::{Duration, Local};
+
+const RESEND_SECONDS: i64 = 60;
+/// Wrong codes accepted from a device, also used for the vouchers
+pub const MAX_ATTEMPTS: u8 = 5;
+
+// Functions
+/// Mails a new code to the guest, within the limits per address and per device. True for the first
+/// code of the device, the only one that earns the short authorization to read the mail
+pub async fn send_email_code(
+    repo: &MongoRepository<Verification>,
+    delivery_repo: &MongoRepository<CodeDelivery>,
+    mailer: &dyn Mailer,
+    config: &EmailVerificationConfig,
+    mac: &str,
+    site: &str,
+    email: &str,
+) -> Result<bool, CustomError> {
+    let previous = previous_codes(repo, VerificationChannel::Email, mac).await?;
+    let first = previous.is_empty();
+
+    check_deliveries(
+        delivery_repo,
+        VerificationChannel::Email,
+        mac,
+        email,
+        config.max_per_address,
+        config.max_per_mac,
+        config.window_minutes,
+    )
+    .await?;
+
+    let code = generator_code(config.code_size, true);
+    let body = config
+        .body
+        .replace("{code}", &code)
+        .replace("{minutes}", &config.code_minutes.to_string());
+
+    if let Err(e) = mailer.send(email, &config.subject, &body).await {
+        println!("{e}");
+        return Err(Error::new_bad_gateway("Verification email could not be sent"));
+    }
+
+    let delivery = CodeDelivery::new(VerificationChannel::Email, mac, site, email, config.window_minutes);
+    delivery_repo.save(delivery).await;
+
+    let verification = Verification::new(VerificationChannel::Email, mac, site, email, code, config.code_minutes);
+    replace_code(repo, previous, verification).await;
+
+    Ok(first)
+}
+
+/// Texts a new code to the guest, within the limits per phone number and per device
+#[allow(clippy::too_many_arguments)]
+pub async fn send_sms_code(
+    repo: &MongoRepository<Verification>,
+    delivery_repo: &MongoRepository<CodeDelivery>,
+    gateway: &dyn SmsGateway,
+    config: &SmsVerificationConfig,
+    mac: &str,
+    site: &str,
+    phone: &str,
+) -> Result<(), CustomError> {
+    let mac = mac.to_lowercase();
+    if phone.trim_start_matches('+').len() < 8 {
+        return Err(Error::new_bad_request("Invalid phone"));
+    }
+
+    let previous = previous_codes(repo, VerificationChannel::Sms, &mac).await?;
+
+    check_deliveries(
+        delivery_repo,
+        VerificationChannel::Sms,
+        &mac,
+        phone,
+        config.max_per_phone,
+        config.max_per_mac,
+        config.window_minutes,
+    )
+    .await?;
+
+    let code = generator_code(config.code_size, true);
+    let message = config
+        .message
+        .replace("{code}", &code)
+        .replace("{minutes}", &config.code_minutes.to_string());
+
+    if let Err(e) = gateway.send(phone, &message).await {
+        println!("{e}");
+        return Err(Error::new_bad_gateway("Verification SMS could not be sent"));
+    }
+
+    let delivery = CodeDelivery::new(VerificationChannel::Sms, &mac, site, phone, config.window_minutes);
+    delivery_repo.save(delivery).await;
+
+    let verification = Verification::new(VerificationChannel::Sms, &mac, site, phone, code, config.code_minutes);
+    replace_code(repo, previous, verification).await;
+
+    Ok(())
+}
+
+/// Consumes the code sent to `target` for the device, refused once expired or after too many attempts
+pub async fn confirm_code(
+    repo: &MongoRepository<Verification>,
+    channel: VerificationChannel,
+    mac: &str,
+    target: &str,
+    code: &str,
+) -> Result<(), BadRequest> {
+    let verification = repo
+        .find(doc! { "mac": mac.to_lowercase(), "target": target.to_lowercase() })
+        .await
+        .into_iter()
+        .find(|v| v.channel == channel);
+
+    let Some(mut verification) = verification else {
+        return Err(Error::new_bad_request("Verification code not requested"));
+    };
+
+    if verification.is_expired() || verification.attempts >= MAX_ATTEMPTS {
+        repo.delete(verification).await;
+        return Err(Error::new_bad_request("Verification code expired, ask for a new one"));
+    }
+
+    if !verify(code.trim(), &verification.code).unwrap_or(false) {
+        verification.attempts += 1;
+        repo.update(verification).await;
+        return Err(Error::new_bad_request("Invalid verification code"));
+    }
+
+    repo.delete(verification).await;
+    Ok(())
+}
+
+/// Devices of the site still waiting to confirm a mailed code, their short authorization is expected.
+/// Texted codes come without one
+pub async fn verifying_devices(repo: &MongoRepository<Verification>, site: &str) -> Vec<String> {
+    repo.find(doc! { "site": site, "channel": bson::to_bson(&VerificationChannel::Email).unwrap() })
+        .await
+        .into_iter()
+        .filter(|v| !v.is_expired())
+        .map(|v| v.mac)
+        .collect()
+}
+
+// Codes already sent to the device on the channel, refused while the last one is too recent
+async fn previous_codes(
+    repo: &MongoRepository<Verification>,
+    channel: VerificationChannel,
+    mac: &str,
+) -> Result<Vec<Verification>, CustomError> {
+    let previous: Vec<Verification> = repo
+        .find(doc! { "mac": mac.to_lowercase() })
+        .await
+        .into_iter()
+        .filter(|v| v.channel == channel)
+        .collect();
+
+    if previous
+        .iter()
+        .any(|v| Local::now() - v.created_at < Duration::seconds(RESEND_SECONDS))
+    {
+        return Err(Error::new_bad_request("Wait before asking for a new code"));
+    }
+
+    Ok(previous)
+}
+
+// Refuses a new code once the target or the device got too many on the channel within the window
+async fn check_deliveries(
+    repo: &MongoRepository<CodeDelivery>,
+    channel: VerificationChannel,
+    mac: &str,
+    target: &str,
+    max_per_target: usize,
+    max_per_mac: usize,
+    window_minutes: i64,
+) -> Result<(), CustomError> {
+    let mac = mac.to_lowercase();
+    let target = target.to_lowercase();
+    let since = DateTime::from_millis((Local::now() - Duration::minutes(window_minutes)).timestamp_millis());
+
+    let recent = repo
+        .find(doc! {
+            "channel": bson::to_bson(&channel).unwrap(),
+            "sent_at": { "$gt": since },
+            "$or": [{ "target": &target }, { "mac": &mac }],
+        })
+        .await;
+
+    let by_target = recent.iter().filter(|d| d.target == target).count();
+    let by_mac = recent.iter().filter(|d| d.mac == mac).count();
+
+    if by_target >= max_per_target || by_mac >= max_per_mac {
+        return Err(Error::new_too_many_requests("Too many codes requested, try again later"));
+    }
+
+    Ok(())
+}
+
+// Keeps only the new code of the device, hashed
+async fn replace_code(repo: &MongoRepository<Verification>, previous: Vec<Verification>, mut verification: Verification) {
+    for v in previous {
+        repo.delete(v).await;
+    }
+
+    verification.code = hash(&verification.code, DEFAULT_COST).unwrap();
+    repo.save(verification).await;
+}
@@ -0,0 +1,798 @@
+use super::TestApp;
+use crate::{
+    configurations::config::{ClientInfo, FieldGroup, RedirectConfig, SuccessPage, UserGroupsConfig},
+    model::{
+        entity::{
+            approver::{Approver, ApproverGroup},
+            blocked_device::BlockedDevice,
+            client::{Client, ClientChangeKind, ClientData, ClientStatus},
+            portal_form::{FieldOption, FieldType, FormField, PortalForm},
+            terms::Terms,
+        },
+        repository::Repository,
+    },
+    security::portal_session::PortalSession,
+    utils::client_events::ClientEvents,
+};
+use bson::doc;
+use chrono::Local;
+use rocket::http::{Accept, Cookie, Header, Status};
+use rocket::local::asynchronous::LocalResponse;
+use rocket::tokio::{io::AsyncReadExt, time::{Duration, timeout}};
+use serde_json::json;
+
+const MAC: &str = "4c:eb:42:9b:82:55";
+
+fn portal(ap: Option<&str>, issued_at: i64) -> Cookie<'static> {
+    let mut session = PortalSession::new(MAC.into(), "default".into(), ap.map(String::from), None, None);
+    session.issued_at = issued_at;
+    session.to_cookie()
+}
+
+// Reads the event stream until `text` shows up
+async fn wait_for(stream: &mut LocalResponse<'_>, text: &str) -> String {
+    let mut received = String::new();
+    let mut buffer = [0; 1024];
+
+    while !received.contains(text) {
+        let read = timeout(Duration::from_secs(5), stream.read(&mut buffer)).await.unwrap().unwrap();
+        assert!(read > 0, "stream closed before {text}");
+        received.push_str(&String::from_utf8_lossy(&buffer[..read]));
+    }
+
+    received
+}
+
+// Guest approved just now for 60 minutes
+async fn approved_client(app: &TestApp) -> Client {
+    app.mock.add_device(MAC, true, false);
+
+    let mut client = Client::new();
+    client.mac = MAC.to_string();
+    client.site = String::from("default");
+    client.status = ClientStatus::Approved;
+    client.time_connection = String::from("60");
+
+    app.repository::<Client>().save(client).await.unwrap()
+}
+
+fn form(approver_code: Option<&str>) -> serde_json::Value {
+    json!({
+        "full_name": "Jane Doe",
+        "email": "jane@example.com",
+        "phone": "5511999999999",
+        "approver_code": approver_code,
+        "fields": {},
+    })
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn admin_direct_approval_authorizes_and_saves_the_client() {
+    let app = TestApp::start().await;
+    let token = app.admin_token().await;
+
+    let res = app
+        .client
+        .post("/api/client/connect")
+        .header(Header::new("Authorization", token))
+        .json(&json!({
+            "id": null,
+            "data": null,
+            "mac": MAC,
+            "site": "default",
+            "minutes": 30,
+            "connect": true,
+            "limits": { "up": 512, "down": null, "bytes": null },
+        }))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(app.mock.commands_for(MAC), vec!["authorize-guest"]);
+    assert_eq!(app.mock.state().commands[0]["up"], 512);
+
+    let clients = app.repository::<Client>().find(doc! { "mac": MAC }).await;
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].status, ClientStatus::Approved);
+    assert_eq!(clients[0].approver, "Test Admin");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn refused_authorization_is_not_saved() {
+    let app = TestApp::start().await;
+    let token = app.admin_token().await;
+    app.mock.state().refuse_commands = true;
+
+    let res = app
+        .client
+        .post("/api/client/connect")
+        .header(Header::new("Authorization", token))
+        .json(&json!({
+            "id": null,
+            "data": null,
+            "mac": MAC,
+            "site": "default",
+            "minutes": 30,
+            "connect": true,
+            "limits": null,
+        }))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::BadGateway);
+    assert!(app.repository::<Client>().find(doc! { "mac": MAC }).await.is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn approver_code_authorizes_the_guest() {
+    let app = TestApp::start().await;
+
+    let approver = Approver {
+        id: String::new(),
+        username: String::from("reception"),
+        email: String::from("reception@example.com"),
+        password: String::new(),
+        secrete_code: String::from("12345678"),
+        validity: None,
+        group: ApproverGroup::DirectApproval,
+        sites: None,
+    };
+    app.repository::<Approver>().save(approver).await.unwrap();
+
+    let res = app
+        .client
+        .post("/api/client/connect?form")
+        .private_cookie(portal(Some("70:a7:41:dd:7a:78"), Local::now().timestamp()))
+        .json(&form(Some("12345678")))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(app.mock.device(MAC).unwrap()["expired"], false);
+    assert_eq!(app.mock.device(MAC).unwrap()["minutes"], 60);
+
+    let client = app.repository::<Client>().find_one(doc! { "mac": MAC }).await.unwrap();
+    assert_eq!(client.status, ClientStatus::Approved);
+    assert_eq!(client.approver, "reception");
+    assert_eq!(client.ap.as_deref(), Some("70:a7:41:dd:7a:78"));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn free_request_waits_for_approval() {
+    let app = TestApp::start().await;
+
+    let res = app
+        .client
+        .post("/api/client/connect?form")
+        .private_cookie(portal(None, Local::now().timestamp()))
+        .json(&form(None))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    assert!(app.mock.commands_for(MAC).is_empty());
+
+    let client = app.repository::<Client>().find_one(doc! { "mac": MAC }).await.unwrap();
+    assert_eq!(client.status, ClientStatus::Pending);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn portal_session_is_only_issued_to_devices_on_the_site() {
+    let app = TestApp::offline().await;
+    app.mock.add_device(MAC, true, true);
+    app.mock.state().devices[0]["ip"] = json!("10.0.0.20");
+
+    let register = |mac: &str, t: i64, ip: &str| {
+        app.client
+            .get(format!("/guest/s/default?ap=70:a7:41:dd:7a:78&id={mac}&t={t}&url=http://example.com&ssid=Guests"))
+            .header(Accept::HTML)
+            .remote(format!("{ip}:40000").parse().unwrap())
+            .dispatch()
+    };
+
+    let now = Local::now().timestamp();
+    let res = register(MAC, now, "10.0.0.20").await;
+    assert_eq!(res.status(), Status::SeeOther);
+
+    let session = res.cookies().get_private("portal").unwrap();
+    let session: PortalSession = serde_json::from_str(session.value()).unwrap();
+    assert_eq!(session.controller.as_deref(), Some("mock"));
+    assert!((session.issued_at - now).abs() <= 1);
+
+    // Unknown device, another address, replayed and forged redirect times
+    assert_eq!(register("00:11:22:33:44:55", now, "10.0.0.20").await.status(), Status::BadRequest);
+    assert_eq!(register(MAC, now, "10.0.0.99").await.status(), Status::BadRequest);
+    assert_eq!(register(MAC, now - 31 * 60, "10.0.0.20").await.status(), Status::BadRequest);
+    assert_eq!(register(MAC, now + 10 * 60, "10.0.0.20").await.status(), Status::BadRequest);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn missing_or_expired_portal_session_is_a_bad_request() {
+    let app = TestApp::start().await;
+    let expired = Local::now().timestamp() - 31 * 60;
+
+    let missing = app.client.post("/api/client/connect?form").json(&form(None)).dispatch().await;
+    assert_eq!(missing.status(), Status::BadRequest);
+    assert!(missing.into_string().await.unwrap().contains("Portal session not found"));
+
+    let res = app
+        .client
+        .post("/api/client/connect?form")
+        .private_cookie(portal(None, expired))
+        .json(&form(None))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::BadRequest);
+    assert!(res.into_string().await.unwrap().contains("Portal session expired"));
+    assert!(app.repository::<Client>().find_one(doc! { "mac": MAC }).await.is_none());
+
+    app.cleanup().await;
+}
+
+#[test]
+fn redirects_only_to_success_pages_or_allowed_hosts() {
+    let redirect = RedirectConfig {
+        success_pages: vec![SuccessPage {
+            site: String::from("lobby"),
+            ssid: Some(String::from("Guests")),
+            url: String::from("https://example.com/welcome"),
+        }],
+        allowed_hosts: vec![String::from("www.example.com"), String::from("*.example.org")],
+    };
+
+    let target = |site, ssid, url| redirect.target_for(site, ssid, Some(url));
+
+    assert_eq!(target("lobby", Some("Guests"), "http://evil.com").as_deref(), Some("https://example.com/welcome"));
+    assert_eq!(target("lobby", Some("Staff"), "http://www.example.com/a").as_deref(), Some("http://www.example.com/a"));
+    assert_eq!(target("default", None, "https://news.example.org/").as_deref(), Some("https://news.example.org/"));
+    assert_eq!(target("default", None, "https://example.org.evil.com/"), None);
+    assert_eq!(target("default", None, "javascript:alert(1)"), None);
+    assert_eq!(target("default", None, "//www.example.com"), None);
+}
+
+#[test]
+fn field_groups_are_matched_in_the_order_written() {
+    let rule = |field: &str, value: &str, group: &str| FieldGroup {
+        field: field.to_string(),
+        value: value.to_string(),
+        group: group.to_string(),
+    };
+
+    let groups = UserGroupsConfig {
+        fields: Some(vec![
+            rule("visitor_type", "contractor", "Contractors"),
+            rule("company", "ACME", "Partners"),
+        ]),
+        default: Some(String::from("Guests")),
+        ..Default::default()
+    };
+
+    let fields = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+
+    let both = fields(&[("company", "ACME"), ("visitor_type", "contractor")]);
+    assert_eq!(groups.group_for(None, None, &both).as_deref(), Some("Contractors"));
+    assert_eq!(groups.group_for(None, None, &fields(&[("company", "ACME")])).as_deref(), Some("Partners"));
+    assert_eq!(groups.group_for(None, None, &fields(&[])).as_deref(), Some("Guests"));
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn approved_client_gets_its_redirect() {
+    let mut app = TestApp::start().await;
+    let session = PortalSession::new(
+        MAC.into(),
+        "default".into(),
+        None,
+        None,
+        Some(String::from("http://www.example.com/news")),
+    );
+
+    let pending = app.client.get("/api/client/redirect").private_cookie(session.to_cookie()).dispatch().await;
+    assert_eq!(pending.status(), Status::BadRequest);
+
+    let mut client = Client::new();
+    client.mac = MAC.to_string();
+    client.site = String::from("default");
+    client.status = ClientStatus::Approved;
+    app.repository::<Client>().save(client).await.unwrap();
+
+    let res = app.client.get("/api/client/redirect").private_cookie(session.to_cookie()).dispatch().await;
+    assert_eq!(res.status(), Status::NotFound);
+
+    app.config.clients.redirect = Some(RedirectConfig {
+        success_pages: vec![],
+        allowed_hosts: vec![String::from("www.example.com")],
+    });
+    app.reload_config().await;
+
+    let res = app.client.get("/api/client/redirect").private_cookie(session.to_cookie()).dispatch().await;
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(res.into_json::<String>().await.unwrap(), "http://www.example.com/news");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn waiting_guest_is_told_of_the_approval() {
+    let app = TestApp::start().await;
+    let token = app.admin_token().await;
+
+    let mut pending = Client::new();
+    pending.mac = MAC.to_string();
+    pending.site = String::from("default");
+    let pending = app.repository::<Client>().save(pending).await.unwrap();
+
+    let mut stream = app
+        .client
+        .get("/api/client/status/events")
+        .private_cookie(portal(None, Local::now().timestamp()))
+        .dispatch()
+        .await;
+
+    assert_eq!(stream.status(), Status::Ok);
+    assert!(wait_for(&mut stream, "\n\n").await.contains("\"Pending\""));
+
+    let res = app
+        .client
+        .post("/api/client/connect")
+        .header(Header::new("Authorization", token))
+        .json(&json!({
+            "id": pending.id,
+            "data": null,
+            "mac": MAC,
+            "site": "default",
+            "minutes": 30,
+            "connect": true,
+            "limits": null,
+        }))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    wait_for(&mut stream, "\"Approved\"").await;
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn status_is_read_from_the_site_of_the_session() {
+    let app = TestApp::start().await;
+
+    let mut pending = Client::new();
+    pending.mac = MAC.to_string();
+    pending.site = String::from("default");
+    app.repository::<Client>().save(pending).await.unwrap();
+
+    // Newer record of the same device on another site
+    let mut elsewhere = approved_client(&app).await;
+    elsewhere.site = String::from("lobby");
+    app.repository::<Client>().update(elsewhere).await.unwrap();
+
+    let mut stream = app
+        .client
+        .get("/api/client/status/events")
+        .private_cookie(portal(None, Local::now().timestamp()))
+        .dispatch()
+        .await;
+
+    assert!(wait_for(&mut stream, "\n\n").await.contains("\"Pending\""));
+
+    let status = app.client.get(format!("/api/client/{MAC}/status?site=default")).dispatch().await;
+    assert_eq!(status.into_string().await.unwrap(), "\"Pending\"");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn approver_extension_is_published() {
+    let app = TestApp::start().await;
+    let client = approved_client(&app).await;
+
+    let approver = Approver {
+        id: String::new(),
+        username: String::from("reception"),
+        email: String::from("reception@example.com"),
+        password: String::new(),
+        secrete_code: String::from("12345678"),
+        validity: None,
+        group: ApproverGroup::AccessRelease,
+        sites: None,
+    };
+    app.repository::<Approver>().save(approver).await.unwrap();
+
+    let mut events = app.client.rocket().state::<ClientEvents>().unwrap().subscribe();
+
+    let res = app
+        .client
+        .put(format!("/api/client/{}/time?approver", client.id))
+        .json(&json!({ "minutes": 30, "approver_code": "12345678" }))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    assert!((89..=90).contains(&app.mock.device(MAC).unwrap()["minutes"].as_i64().unwrap()));
+    assert_eq!(events.try_recv().unwrap().client_id, client.id);
+
+    let saved = app.repository::<Client>().find_by_id(client.id).await.unwrap();
+    assert_eq!(saved.changes.unwrap()[0].kind, ClientChangeKind::Extended);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn approver_extension_is_capped() {
+    let mut app = TestApp::start().await;
+    app.config.approvers.max_extension_minutes = Some(120);
+    app.reload_config().await;
+
+    let client = approved_client(&app).await;
+    let approver = Approver {
+        id: String::new(),
+        username: String::from("reception"),
+        email: String::from("reception@example.com"),
+        password: String::new(),
+        secrete_code: String::from("12345678"),
+        validity: None,
+        group: ApproverGroup::AccessRelease,
+        sites: None,
+    };
+    app.repository::<Approver>().save(approver).await.unwrap();
+
+    let res = app
+        .client
+        .put(format!("/api/client/{}/time?approver", client.id))
+        .json(&json!({ "minutes": 180, "approver_code": "12345678" }))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::BadRequest);
+    assert_eq!(app.mock.commands_for(MAC), Vec::<String>::new());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn stored_session_matches_the_controller_limit() {
+    let app = TestApp::start().await;
+    let token = app.admin_token().await;
+    let client = approved_client(&app).await;
+
+    let res = app
+        .client
+        .put(format!("/api/client/{}/time", client.id))
+        .header(Header::new("Authorization", token))
+        .json(&json!({ "minutes": 100_000, "approver_code": null }))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(app.mock.device(MAC).unwrap()["minutes"], u16::MAX);
+
+    // The record ends when the controller does, not 100000 minutes later
+    let saved = app.repository::<Client>().find_by_id(client.id).await.unwrap();
+    let remaining = (saved.end_time() - Local::now()).num_minutes();
+    assert!((u16::MAX as i64 - 1..=u16::MAX as i64).contains(&remaining));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn admin_can_shorten_a_session() {
+    let app = TestApp::start().await;
+    let token = app.admin_token().await;
+    let client = approved_client(&app).await;
+
+    let res = app
+        .client
+        .put(format!("/api/client/{}/time", client.id))
+        .header(Header::new("Authorization", token))
+        .json(&json!({ "minutes": -30, "approver_code": null }))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    assert!((29..=30).contains(&app.mock.device(MAC).unwrap()["minutes"].as_i64().unwrap()));
+
+    let saved = app.repository::<Client>().find_by_id(client.id).await.unwrap();
+    assert_eq!(saved.status, ClientStatus::Approved);
+    assert_eq!(saved.time_connection, "30");
+    assert_eq!(saved.changes.unwrap()[0].kind, ClientChangeKind::Shortened);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn ending_a_session_unauthorizes_and_expires_the_client() {
+    let app = TestApp::start().await;
+    let token = app.admin_token().await;
+    let client = approved_client(&app).await;
+
+    let res = app
+        .client
+        .delete(format!("/api/client/{}/session", client.id))
+        .header(Header::new("Authorization", token))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(app.mock.commands_for(MAC), vec!["unauthorize-guest"]);
+    assert_eq!(app.mock.device(MAC).unwrap()["expired"], true);
+
+    let saved = app.repository::<Client>().find_by_id(client.id).await.unwrap();
+    assert_eq!(saved.status, ClientStatus::Expired);
+    assert_eq!(saved.changes.unwrap()[0].kind, ClientChangeKind::Ended);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn voucher_is_redeemed_up_to_its_quota() {
+    let app = TestApp::start().await;
+    let id = app.mock.add_voucher("1234567890", 120, 1);
+
+    let redeem = || {
+        app.client
+            .post("/api/client/voucher")
+            .private_cookie(portal(None, Local::now().timestamp()))
+            .json(&json!({ "code": "12345-67890", "data": null, "terms_version": null }))
+            .dispatch()
+    };
+
+    assert_eq!(redeem().await.status(), Status::Ok);
+    assert_eq!(app.mock.device(MAC).unwrap()["minutes"], 120);
+    assert!(app.mock.voucher(&id).is_none());
+
+    let client = app.repository::<Client>().find_one(doc! { "mac": MAC }).await.unwrap();
+    assert_eq!(client.status, ClientStatus::Approved);
+    assert_eq!(client.approver, "voucher:1234567890");
+
+    assert_eq!(redeem().await.status(), Status::BadRequest);
+    assert_eq!(app.mock.commands_for(MAC), vec!["authorize-guest"]);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn concurrent_redemptions_share_the_voucher_quota() {
+    let app = TestApp::start().await;
+    app.mock.add_voucher("1234567890", 120, 1);
+
+    // The unique index is built in the background at launch
+    let redemptions = app.database.collection::<bson::Document>("VoucherRedemptions");
+    while redemptions.list_index_names().await.unwrap_or_default().len() < 2 {
+        rocket::tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let redeem = || {
+        app.client
+            .post("/api/client/voucher")
+            .private_cookie(portal(None, Local::now().timestamp()))
+            .json(&json!({ "code": "12345-67890", "data": null, "terms_version": null }))
+            .dispatch()
+    };
+
+    let (first, second) = rocket::tokio::join!(redeem(), redeem());
+    let mut statuses = vec![first.status(), second.status()];
+    statuses.sort_by_key(|s| s.code);
+
+    assert_eq!(statuses, vec![Status::Ok, Status::BadRequest]);
+    assert_eq!(app.mock.commands_for(MAC), vec!["authorize-guest"]);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn voucher_guesses_are_limited_per_device() {
+    let app = TestApp::start().await;
+    app.mock.add_voucher("1234567890", 120, 1);
+
+    let redeem = |code: &'static str| {
+        app.client
+            .post("/api/client/voucher")
+            .private_cookie(portal(None, Local::now().timestamp()))
+            .json(&json!({ "code": code, "data": null, "terms_version": null }))
+            .dispatch()
+    };
+
+    for _ in 0..5 {
+        assert_eq!(redeem("00000-00000").await.status(), Status::BadRequest);
+    }
+
+    // Even the right code is refused until the window ends
+    assert_eq!(redeem("12345-67890").await.status(), Status::TooManyRequests);
+    assert!(app.mock.commands_for(MAC).is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn blocked_device_cannot_request_access() {
+    let app = TestApp::start().await;
+    let token = app.admin_token().await;
+
+    let res = app
+        .client
+        .post("/api/blocklist")
+        .header(Header::new("Authorization", token.clone()))
+        .json(&json!({ "mac": MAC, "site": "default", "controller": null, "reason": "abuse", "minutes": null }))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Created);
+    assert_eq!(app.mock.device(MAC).unwrap()["blocked"], true);
+
+    let res = app
+        .client
+        .post("/api/client/connect?form")
+        .private_cookie(portal(None, Local::now().timestamp()))
+        .json(&form(None))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Unauthorized);
+    assert!(app.repository::<Client>().find(doc! { "mac": MAC }).await.is_empty());
+
+    let entry = app.repository::<BlockedDevice>().find_one(doc! { "mac": MAC }).await.unwrap();
+    let res = app
+        .client
+        .delete(format!("/api/blocklist/{}", entry.id))
+        .header(Header::new("Authorization", token))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Ok);
+    assert_eq!(app.mock.commands_for(MAC), vec!["block-sta", "unblock-sta"]);
+
+    app.cleanup().await;
+}
+
+#[test]
+fn form_errors_are_reported_per_field() {
+    let field = |name: &str, kind, required| FormField {
+        name: name.to_string(),
+        label: [(String::from("en"), name.to_string())].into(),
+        kind,
+        required,
+        options: None,
+        regex: None,
+        error_message: None,
+    };
+
+    let schema = PortalForm {
+        site: Some(String::from("default")),
+        ssid: Some(String::from("Guests")),
+        fields: vec![
+            field("full_name", FieldType::Text, true),
+            FormField { error_message: Some(String::from("Use your work email")), ..field("email", FieldType::Email, true) },
+            field("phone", FieldType::Phone, false),
+            FormField {
+                options: Some(vec![FieldOption { value: String::from("contractor"), label: Default::default() }]),
+                ..field("visitor_type", FieldType::Select, true)
+            },
+            field("terms", FieldType::Checkbox, true),
+            FormField { regex: Some(String::from("^19|20")), ..field("birth", FieldType::Date, false) },
+        ],
+    };
+
+    let mut data: ClientData = serde_json::from_value(form(None)).unwrap();
+    data.email = String::from("jane");
+    data.phone = String::new();
+    data.fields = [
+        (String::from("visitor_type"), String::from("vendor")),
+        (String::from("terms"), String::from("false")),
+        (String::from("birth"), String::from("2001-02-30")),
+    ]
+    .into();
+
+    let errors = data.validate_form(&schema).unwrap_err();
+
+    assert_eq!(errors.len(), 4);
+    assert_eq!(errors["email"], "Use your work email");
+    assert_eq!(errors["visitor_type"], "Invalid value");
+    assert_eq!(errors["terms"], "Invalid value");
+    assert_eq!(errors["birth"], "Invalid value");
+
+    data.email = String::from("jane@example.com");
+    data.fields = [
+        (String::from("visitor_type"), String::from("contractor")),
+        (String::from("terms"), String::from("true")),
+        (String::from("birth"), String::from("2001-02-28")),
+    ]
+    .into();
+
+    data.validate_form(&schema).unwrap();
+    assert!(schema.matches("default", Some("Guests")));
+    assert!(!schema.matches("default", Some("Staff")));
+}
+
+#[test]
+fn legacy_info_still_validates_the_form() {
+    let info = ClientInfo {
+        name_validated: Some(String::from(r"^\w+ \w+$")),
+        email_validated: None,
+        phone_validated: None,
+        fields: vec![(String::from("company"), String::from(".+"))],
+    };
+
+    let schema = PortalForm::new_with_info(&info);
+    let data: ClientData = serde_json::from_value(form(None)).unwrap();
+    let errors = data.validate_form(&schema).unwrap_err();
+
+    assert_eq!(schema.fields.len(), 2);
+    assert_eq!(errors.keys().collect::<Vec<_>>(), vec!["company"]);
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn submissions_must_accept_the_current_terms() {
+    let app = TestApp::start().await;
+    let token = app.admin_token().await;
+
+    for content in ["First version", "Second version"] {
+        let res = app
+            .client
+            .post("/api/terms")
+            .header(Header::new("Authorization", token.clone()))
+            .json(&json!({ "title": "Acceptable use", "content": content }))
+            .dispatch()
+            .await;
+
+        assert_eq!(res.status(), Status::Created);
+    }
+
+    let current = app.client.get("/api/terms").dispatch().await.into_json::<Terms>().await.unwrap();
+    assert_eq!(current.version, 2);
+
+    let submit = |terms_version: Option<u32>| {
+        let mut data = form(None);
+        data["terms_version"] = json!(terms_version);
+
+        app.client
+            .post("/api/client/connect?form")
+            .private_cookie(portal(None, Local::now().timestamp()))
+            .remote("10.0.0.7:50000".parse().unwrap())
+            .json(&data)
+    };
+
+    for outdated in [None, Some(1)] {
+        let res = submit(outdated).dispatch().await;
+        assert_eq!(res.status(), Status::BadRequest);
+    }
+    assert!(app.repository::<Client>().find_one(doc! { "mac": MAC }).await.is_none());
+
+    let res = submit(Some(2)).dispatch().await;
+    assert_eq!(res.status(), Status::Ok);
+
+    let client = app.repository::<Client>().find_one(doc! { "mac": MAC }).await.unwrap();
+    let terms = client.terms.unwrap();
+    assert_eq!(terms.version, 2);
+    assert_eq!(terms.mac, MAC);
+    assert_eq!(terms.ip.as_deref(), Some("10.0.0.7"));
+
+    app.cleanup().await;
+}
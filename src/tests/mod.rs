@@ -0,0 +1,147 @@
+// Mods
+mod client_flow;
+mod monitoring;
+mod smtp_sink;
+mod unifi_controller;
+mod verification;
+
+use crate::{
+    build_rocket,
+    configurations::config::{ConfigApp, ConfigApplication},
+    glpi::glpi::GLPI,
+    model::{
+        entity::admin::Admin,
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    security::auth_jwt::create_token,
+    unifi::{mock::MockUnifi, unifi::{UnifiControllers, UnifiPlatform}},
+    utils::{client_events::ClientEvents, mailer::SmtpMailer, sms::LogGateway},
+};
+use smtp_sink::SmtpSink;
+use bson::oid::ObjectId;
+use std::sync::Arc;
+use rocket::local::asynchronous::Client;
+use rocket_db_pools::mongodb::{self, Database};
+use serde_json::json;
+
+/// MongoDB server (without database) used by the tests that persist data, run them with `cargo test -- --ignored`
+pub const TEST_DATABASE_VAR: &str = "UNIFI_CONNECT_TEST_DATABASE";
+const OFFLINE_DATABASE: &str = "mongodb://127.0.0.1:9/unifi_connect_offline";
+
+// Structs
+/// Application wired to a mock controller and to a throwaway database
+pub struct TestApp {
+    pub client: Client,
+    pub mock: MockUnifi,
+    pub config: ConfigApplication,
+    pub database: Database,
+    /// Texts sent by the application
+    pub sms: LogGateway,
+    /// Relay receiving the mails sent by the application
+    pub mail: SmtpSink,
+}
+
+// Impls
+impl TestApp {
+    pub async fn start() -> Self {
+        Self::with_database(&test_database_url()).await
+    }
+
+    /// For the tests that never query the database, the driver only connects on the first query
+    pub async fn offline() -> Self {
+        Self::with_database(OFFLINE_DATABASE).await
+    }
+
+    async fn with_database(database_url: &str) -> Self {
+        let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+        let config = test_config(&mock, database_url);
+
+        let database = mongodb::Client::with_uri_str(database_url)
+            .await
+            .unwrap()
+            .default_database()
+            .unwrap();
+
+        let unifi = UnifiControllers::new(&config.unifi).await;
+        let glpi = GLPI::new(String::new(), String::new(), String::new());
+        let sms = LogGateway::default();
+        let mail = SmtpSink::start().await;
+        let mailer = Arc::new(SmtpMailer::new(&mail.config()).unwrap());
+
+        let rocket = build_rocket(config.clone(), unifi, glpi, ClientEvents::new(), Arc::new(sms.clone()), Some(mailer));
+        let client = Client::tracked(rocket).await.unwrap();
+
+        Self { client, mock, config, database, sms, mail }
+    }
+
+    pub fn repository<E>(&self) -> MongoRepository<E> {
+        MongoRepository { database: self.database.clone(), _phantom: Default::default() }
+    }
+
+    pub async fn admin_token(&self) -> String {
+        let admin = Admin {
+            id: String::new(),
+            name: String::from("Test Admin"),
+            username: String::from("admin"),
+            password: None,
+        };
+
+        let admin = self.repository::<Admin>().save(admin).await.unwrap();
+        format!("Bearer {}", create_token(&admin.id, self.config.server.secret_key.clone(), 1))
+    }
+
+    /// Hands the changes made to `config` to the running application
+    pub async fn reload_config(&self) {
+        let state = self.client.rocket().state::<ConfigApp>().unwrap();
+        *state.write().await = self.config.clone();
+    }
+
+    pub async fn cleanup(&self) {
+        let _ = self.database.drop(None).await;
+    }
+}
+
+// Functions
+pub fn test_database_url() -> String {
+    let url = std::env::var(TEST_DATABASE_VAR)
+        .unwrap_or_else(|_| panic!("{TEST_DATABASE_VAR} must point to a MongoDB server to run this test"));
+
+    format!("{}/unifi_connect_test_{}", url.trim_end_matches('/'), ObjectId::new())
+}
+
+pub fn test_config(mock: &MockUnifi, database_url: &str) -> ConfigApplication {
+    let config = json!({
+        "server": {
+            "address": "127.0.0.1",
+            "port": 0,
+            "workers": 1,
+            "log_level": "off",
+            "keep_alive": 5,
+            "secret_key": "a".repeat(64),
+            "files_dir": std::env::temp_dir(),
+        },
+        "database": { "url": database_url, "username": "", "password": "" },
+        "unifi": [ mock.config(UnifiPlatform::Legacy) ],
+        "clients": {
+            "free_request": true,
+            "time_connection": 60,
+            "expiration_time": null,
+            "info": null,
+            "events": false,
+            "limits": null,
+            "groups_limits": null,
+        },
+        "approvers": {
+            "code_size": 8,
+            "just_numbers": true,
+            "validity_days_code": 1,
+            "encrypted_code": false,
+        },
+        "admins": { "token_expirantion": 1 },
+        "users": { "registrations_open": true },
+        "ldap": null,
+        "glpi": null,
+    });
+
+    serde_json::from_value(config).unwrap()
+}
@@ -0,0 +1,183 @@
+use super::TestApp;
+use crate::{
+    configurations::config::{WlanRotation, WlansConfig},
+    model::{
+        entity::{
+            client::{Client, ClientStatus},
+            psk_rotation::PskRotation,
+            reconciliation::Reconciliation,
+            session::Session,
+        },
+        repository::Repository,
+    },
+    unifi::unifi::UnifiControllers,
+    utils::{
+        client_events::ClientEvents,
+        monitoring::{ClientsMonitoring, WlanMonitoring},
+        wlan,
+    },
+};
+use bson::doc;
+use serde_json::json;
+
+const EXPIRED_MAC: &str = "4c:eb:42:9b:82:55";
+const ACTIVE_MAC: &str = "70:a7:41:dd:7a:78";
+
+fn approved(mac: &str) -> Client {
+    let mut client = Client::new();
+    client.mac = mac.to_string();
+    client.site = String::from("default");
+    client.status = ClientStatus::Approved;
+    client.time_connection = String::from("60");
+    client
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn expires_clients_the_controller_no_longer_authorizes() {
+    let app = TestApp::start().await;
+    let repository = app.repository::<Client>();
+
+    repository.save(approved(EXPIRED_MAC)).await.unwrap();
+    repository.save(approved(ACTIVE_MAC)).await.unwrap();
+    app.mock.add_device(EXPIRED_MAC, true, true);
+    app.mock.add_device(ACTIVE_MAC, true, false);
+
+    let unifi = UnifiControllers::new(&app.config.unifi).await;
+    let mut monitoring = ClientsMonitoring::new(app.database.clone(), unifi, app.config.clients.clone(), ClientEvents::new());
+    monitoring.all().await;
+
+    let expired = repository.find_one(doc! { "mac": EXPIRED_MAC }).await.unwrap();
+    let active = repository.find_one(doc! { "mac": ACTIVE_MAC }).await.unwrap();
+
+    assert_eq!(expired.status, ClientStatus::Expired);
+    assert_eq!(active.status, ClientStatus::Approved);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn keeps_one_session_per_association() {
+    let app = TestApp::start().await;
+    let client = app.repository::<Client>().save(approved(ACTIVE_MAC)).await.unwrap();
+    app.mock.add_device(ACTIVE_MAC, true, false);
+
+    let unifi = UnifiControllers::new(&app.config.unifi).await;
+    let mut monitoring = ClientsMonitoring::new(app.database.clone(), unifi, app.config.clients.clone(), ClientEvents::new());
+
+    let associate = |assoc_time: u64, bytes: usize| {
+        let mut state = app.mock.state();
+        let device = &mut state.devices[0];
+        device["assoc_time"] = json!(assoc_time);
+        device["tx_bytes"] = json!(bytes);
+        device["rx_bytes"] = json!(bytes * 2);
+    };
+
+    associate(1_700_000_000, 100);
+    monitoring.all().await;
+    associate(1_700_000_000, 300);
+    monitoring.all().await;
+    associate(1_700_003_600, 50);
+    monitoring.all().await;
+
+    let mut sessions = app.repository::<Session>().find(doc! { "client_id": client.id }).await;
+    sessions.sort_by_key(|s| s.start);
+
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions[0].end.is_some());
+    assert_eq!(sessions[0].tx_bytes, 300);
+    assert_eq!(sessions[0].rx_bytes, 600);
+    assert!(sessions[1].end.is_none());
+    assert_eq!(sessions[1].tx_bytes, 50);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn reports_and_unauthorizes_unknown_guests() {
+    let mut app = TestApp::start().await;
+    app.config.clients.unauthorize_unknown = Some(true);
+
+    let stale = app.repository::<Client>().save(approved(EXPIRED_MAC)).await.unwrap();
+    app.mock.add_device(ACTIVE_MAC, true, false);
+
+    let unifi = UnifiControllers::new(&app.config.unifi).await;
+    let mut monitoring = ClientsMonitoring::new(app.database.clone(), unifi, app.config.clients.clone(), ClientEvents::new());
+    monitoring.all().await;
+
+    let report = app
+        .repository::<Reconciliation>()
+        .find_one(doc! { "site": "default" })
+        .await
+        .unwrap();
+
+    assert_eq!(report.unknown_guests.len(), 1);
+    assert_eq!(report.unknown_guests[0].mac, ACTIVE_MAC);
+    assert!(report.unknown_guests[0].unauthorized);
+    assert_eq!(app.mock.commands_for(ACTIVE_MAC), vec!["unauthorize-guest"]);
+
+    assert_eq!(report.unauthorized_clients.len(), 1);
+    assert_eq!(report.unauthorized_clients[0].client_id, stale.id);
+    assert!(report.unauthorized_clients[0].missing);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn guests_approved_during_the_cycle_are_not_unauthorized() {
+    let mut app = TestApp::start().await;
+    app.config.clients.unauthorize_unknown = Some(true);
+    app.mock.add_device(ACTIVE_MAC, true, false);
+
+    let unifi = UnifiControllers::new(&app.config.unifi).await;
+    let controller = unifi.all()[0].clone();
+    let devices = controller.get_guest_devices(String::from("default")).await.unwrap();
+    let monitoring = ClientsMonitoring::new(app.database.clone(), unifi, app.config.clients.clone(), ClientEvents::new());
+
+    // Saved after the clients of the cycle were read
+    app.repository::<Client>().save(approved(ACTIVE_MAC)).await.unwrap();
+    let report = monitoring.reconcile(&controller, "default", &[], &devices).await;
+
+    assert!(report.unknown_guests.is_empty());
+    assert!(app.mock.commands_for(ACTIVE_MAC).is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn rotates_the_guest_passphrase_once_due() {
+    let app = TestApp::start().await;
+
+    let mut config = WlansConfig {
+        rotate: vec![WlanRotation { site: String::from("default"), ssid: String::from("Guests") }],
+        rotation_hours: 24,
+        passphrase_size: 4,
+        just_numbers: false,
+        kiosk_token: None,
+    };
+
+    let unifi = UnifiControllers::new(&app.config.unifi).await;
+    WlanMonitoring::new(app.database.clone(), unifi.clone(), config.clone()).rotate_due().await;
+
+    // The passphrase already set is kept on the first run
+    let current = wlan::current_passphrases(&app.repository(), &config).await;
+    assert_eq!(current[0].passphrase, "first-passphrase");
+    assert_eq!(current[0].by, "controller");
+
+    config.rotation_hours = 0;
+    WlanMonitoring::new(app.database.clone(), unifi, config.clone()).rotate_due().await;
+
+    let current = wlan::current_passphrases(&app.repository(), &config).await;
+    let passphrase = app.mock.state().wlans[0]["x_passphrase"].clone();
+
+    assert_eq!(current[0].by, "schedule");
+    assert_eq!(current[0].passphrase.len(), 8);
+    assert_eq!(passphrase, current[0].passphrase.as_str());
+    assert_eq!(app.repository::<PskRotation>().find_all().await.len(), 2);
+
+    app.cleanup().await;
+}
@@ -0,0 +1,87 @@
+use std::sync::{Arc, Mutex};
+
+use rocket::tokio::{
+    self,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::configurations::config::{SmtpConfig, SmtpSecurity};
+
+// Structs
+/// SMTP server keeping every message it receives, standing in for the mail relay
+pub struct SmtpSink {
+    pub port: u16,
+    pub messages: Arc<Mutex<Vec<String>>>,
+}
+
+// Impls
+impl SmtpSink {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let messages: Arc<Mutex<Vec<String>>> = Arc::default();
+
+        let store = messages.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(session(socket, store.clone()));
+            }
+        });
+
+        Self { port, messages }
+    }
+
+    pub fn config(&self) -> SmtpConfig {
+        SmtpConfig {
+            host: String::from("127.0.0.1"),
+            port: self.port,
+            username: None,
+            password: None,
+            from: String::from("Guest Wi-Fi <wifi@example.com>"),
+            security: SmtpSecurity::None,
+        }
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+// Functions
+async fn session(socket: TcpStream, messages: Arc<Mutex<Vec<String>>>) {
+    let (read, mut write) = socket.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut data: Option<String> = None;
+
+    let _ = write.write_all(b"220 sink ESMTP\r\n").await;
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(body) = data.as_mut() {
+            if line == "." {
+                messages.lock().unwrap().push(data.take().unwrap());
+                let _ = write.write_all(b"250 OK\r\n").await;
+            } else {
+                body.push_str(&line);
+                body.push('\n');
+            }
+
+            continue;
+        }
+
+        let command = line.to_uppercase();
+        let reply = if command.starts_with("EHLO") {
+            "250-sink\r\n250 8BITMIME\r\n"
+        } else if command.starts_with("DATA") {
+            data = Some(String::new());
+            "354 End data with <CR><LF>.<CR><LF>\r\n"
+        } else if command.starts_with("QUIT") {
+            let _ = write.write_all(b"221 Bye\r\n").await;
+            break;
+        } else {
+            "250 OK\r\n"
+        };
+
+        let _ = write.write_all(reply.as_bytes()).await;
+    }
+}
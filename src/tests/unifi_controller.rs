@@ -0,0 +1,308 @@
+use crate::{
+    configurations::config::UnifiConfig,
+    model::entity::{
+        client::{Client, ClientLimits},
+        guest_portal::{GuestAuth, GuestPortal},
+    },
+    unifi::{
+        error::UnifiError,
+        mock::MockUnifi,
+        unifi::{UnifiController, UnifiControllers, UnifiPlatform, VoucherCreation},
+    },
+};
+
+const MAC: &str = "4c:eb:42:9b:82:55";
+
+#[tokio::test]
+async fn authorizes_guest_with_limits() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    let limits = ClientLimits { up: Some(256), down: None, bytes: Some(500) };
+    unifi.authorize_device("default", MAC, 60, &limits).await.unwrap();
+
+    let state = mock.state();
+    let command = state.commands.last().unwrap();
+
+    assert_eq!(command["cmd"], "authorize-guest");
+    assert_eq!(command["mac"], MAC);
+    assert_eq!(command["minutes"], 60);
+    assert_eq!(command["up"], 256);
+    assert_eq!(command["bytes"], 500);
+    assert!(command.get("down").is_none());
+    assert_eq!(state.logins, 1);
+}
+
+#[tokio::test]
+async fn logs_in_again_when_the_session_is_dropped() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    unifi.authorize_device("default", MAC, 60, &ClientLimits::default()).await.unwrap();
+    mock.expire_session();
+    unifi.unauthorize_device("default", MAC).await.unwrap();
+
+    assert_eq!(mock.state().logins, 2);
+    assert_eq!(mock.commands_for(MAC), vec!["authorize-guest", "unauthorize-guest"]);
+    assert_eq!(mock.device(MAC).unwrap()["expired"], true);
+}
+
+#[tokio::test]
+async fn refused_commands_are_api_errors() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+    mock.state().refuse_commands = true;
+
+    let res = unifi.authorize_device("default", MAC, 60, &ClientLimits::default()).await;
+
+    assert!(matches!(res, Err(UnifiError::Api(msg)) if msg == "api.err.UnknownStation"));
+    assert!(mock.commands_for(MAC).is_empty());
+}
+
+#[tokio::test]
+async fn rejected_login_is_an_auth_error() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    mock.state().refuse_login = true;
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    let res = unifi.get_guest_devices(String::from("default")).await;
+
+    assert!(matches!(res, Err(UnifiError::Auth(_))));
+}
+
+#[tokio::test]
+async fn detects_the_platform_of_the_controller() {
+    for platform in [UnifiPlatform::Legacy, UnifiPlatform::UnifiOs] {
+        let mock = MockUnifi::start(platform).await;
+        let unifi = UnifiController::new(&mock.config(UnifiPlatform::Auto)).await;
+
+        // Writes on UniFi OS are refused by the mock without the CSRF token of the login
+        unifi.authorize_device("default", MAC, 30, &ClientLimits::default()).await.unwrap();
+        let guests = unifi.get_guest_devices(String::from("default")).await.unwrap();
+
+        assert_eq!(guests.len(), 1);
+        assert_eq!(guests[0].expired, Some(false));
+    }
+}
+
+#[tokio::test]
+async fn connecting_a_client_renames_its_device() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    mock.add_device(MAC, true, true);
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    let mut client = Client::new();
+    client.mac = MAC.to_string();
+    client.site = String::from("default");
+    client.full_name = String::from("Jane Doe");
+    client.time_connection = String::from("90");
+
+    unifi.conect_client(&client).await.unwrap();
+
+    let device = mock.device(MAC).unwrap();
+    assert_eq!(device["expired"], false);
+    assert_eq!(device["minutes"], 90);
+    assert_eq!(mock.state().renames, vec![(String::from("user-0"), String::from("(Jane Doe)"))]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_requests_share_one_login() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+    mock.expire_session();
+
+    let requests: Vec<_> = (0..8)
+        .map(|i| {
+            let unifi = unifi.clone();
+            tokio::spawn(async move {
+                let mac = format!("4c:eb:42:9b:82:{i:02}");
+                unifi.authorize_device("default", &mac, 60, &ClientLimits::default()).await
+            })
+        })
+        .collect();
+
+    for request in requests {
+        request.await.unwrap().unwrap();
+    }
+
+    assert_eq!(mock.state().logins, 2);
+    assert_eq!(mock.state().commands.len(), 8);
+}
+
+#[tokio::test]
+async fn lists_only_access_points() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    mock.add_network_device("70:a7:41:dd:7a:78", "uap", 3);
+    mock.add_network_device("70:a7:41:dd:7a:79", "usw", 0);
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    let devices = unifi.get_network_devices("default").await.unwrap();
+    let aps = unifi.get_access_points("default").await.unwrap();
+
+    assert_eq!(devices.len(), 2);
+    assert_eq!(aps.len(), 1);
+    assert_eq!(aps[0].mac, "70:a7:41:dd:7a:78");
+    assert_eq!(aps[0].guests, Some(3));
+    assert_eq!(aps[0].uptime, Some(3600));
+}
+
+#[tokio::test]
+async fn updates_the_guest_portal_keeping_unmanaged_keys() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    let mut setting = unifi.get_guest_access("default").await.unwrap();
+    let mut portal = GuestPortal::new_with_setting(&setting);
+
+    assert_eq!(portal.auth, GuestAuth::Hotspot);
+    assert_eq!(portal.allowed_subnets, vec!["10.0.0.0/24", "192.168.50.0/24"]);
+    assert_eq!(portal.allowed_hosts, vec!["portal.example.com"]);
+
+    portal.auth = GuestAuth::Custom;
+    assert!(portal.validate().is_err());
+
+    portal.portal_ip = Some(String::from("10.0.0.5"));
+    portal.redirect_url = Some(String::from("https://example.com/welcome"));
+    portal.allowed_subnets = vec![String::from("10.0.0.0/24")];
+    portal.validate().unwrap();
+
+    portal.apply(&mut setting);
+    let setting = unifi.set_guest_access("default", &setting).await.unwrap();
+    let saved = mock.state().guest_access.clone();
+
+    assert_eq!(saved["auth"], "custom");
+    assert_eq!(saved["custom_ip"], "10.0.0.5");
+    assert_eq!(saved["redirect_enabled"], true);
+    assert_eq!(saved["expire"], 480);
+    assert_eq!(saved["allowed_subnet_3"], "");
+    assert_eq!(GuestPortal::new_with_setting(&setting).allowed_hosts, vec!["portal.example.com"]);
+}
+
+#[test]
+fn rejects_invalid_guest_portal_fields() {
+    let portal = GuestPortal {
+        auth: GuestAuth::Hotspot,
+        portal_ip: None,
+        portal_hostname: None,
+        redirect_url: None,
+        redirect_https: false,
+        allowed_subnets: vec![],
+        allowed_hosts: vec![],
+    };
+
+    let invalid = [
+        GuestPortal { portal_ip: Some(String::from("portal")), ..portal.clone() },
+        GuestPortal { redirect_url: Some(String::from("ftp://example.com")), ..portal.clone() },
+        GuestPortal { allowed_subnets: vec![String::from("10.0.0.0/33")], ..portal.clone() },
+        GuestPortal { allowed_hosts: vec![String::from("not a host")], ..portal.clone() },
+    ];
+
+    portal.validate().unwrap();
+    assert!(invalid.iter().all(|p| p.validate().is_err()));
+}
+
+#[tokio::test]
+async fn connecting_a_client_moves_it_to_its_user_group() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    mock.add_device(MAC, true, true);
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    let mut client = Client::new();
+    client.mac = MAC.to_string();
+    client.site = String::from("default");
+    client.time_connection = String::from("60");
+    client.user_group = Some(String::from("Staff Guests"));
+
+    unifi.conect_client(&client).await.unwrap();
+    assert_eq!(mock.device(MAC).unwrap()["usergroup_id"], "group-staff");
+
+    // An unknown group leaves the guest authorized in the group it already had
+    client.user_group = Some(String::from("Missing"));
+    unifi.conect_client(&client).await.unwrap();
+    assert_eq!(mock.device(MAC).unwrap()["usergroup_id"], "group-staff");
+    assert_eq!(mock.commands_for(MAC), vec!["authorize-guest", "authorize-guest"]);
+}
+
+#[tokio::test]
+async fn guests_are_routed_to_the_controller_they_are_on() {
+    let first = MockUnifi::start(UnifiPlatform::Legacy).await;
+    let second = MockUnifi::start(UnifiPlatform::Legacy).await;
+    second.add_device(MAC, true, true);
+
+    // Both controllers have a "default" site
+    let configs = [
+        UnifiConfig { name: String::from("first"), ..first.config(UnifiPlatform::Legacy) },
+        UnifiConfig { name: String::from("second"), ..second.config(UnifiPlatform::Legacy) },
+    ];
+    let unifi = UnifiControllers::new(&configs).await;
+    unifi.get_sites().await.unwrap();
+
+    let located = unifi.locate_station("default", MAC, None).await.unwrap();
+    assert_eq!(located.name(), "second");
+
+    let mut client = Client::new();
+    client.mac = MAC.to_string();
+    client.site = String::from("default");
+    client.controller = Some(located.name().to_string());
+    client.time_connection = String::from("60");
+
+    unifi.for_client(&client).conect_client(&client).await.unwrap();
+
+    assert!(first.commands_for(MAC).is_empty());
+    assert_eq!(second.commands_for(MAC), vec!["authorize-guest"]);
+    assert_eq!(unifi.for_site("default").name(), "first");
+}
+
+#[tokio::test]
+async fn sites_are_read_from_the_controllers_that_answer() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+
+    let configs = [
+        UnifiConfig { name: String::from("down"), url: String::from("http://127.0.0.1:9"), ..mock.config(UnifiPlatform::Legacy) },
+        UnifiConfig { name: String::from("up"), ..mock.config(UnifiPlatform::Legacy) },
+    ];
+    let unifi = UnifiControllers::new(&configs).await;
+
+    let sites = unifi.get_sites().await.unwrap();
+    assert_eq!(sites.len(), 1);
+    assert_eq!(sites[0].controller, "up");
+
+    let unifi = UnifiControllers::new(&configs[..1]).await;
+    assert!(unifi.get_sites().await.is_err());
+}
+
+#[tokio::test]
+async fn creates_lists_and_revokes_vouchers() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    let limits = ClientLimits { up: Some(512), down: None, bytes: None };
+    let created = unifi
+        .create_vouchers("default", VoucherCreation::new(2, 90, 1, Some(String::from("event")), &limits))
+        .await
+        .unwrap();
+
+    assert_eq!(created.len(), 2);
+    assert!(created.iter().all(|v| v.duration == Some(90) && v.quota == Some(1)));
+    assert_eq!(created[0].limits(), limits);
+
+    unifi.revoke_voucher("default", created[0].id.clone()).await.unwrap();
+    let vouchers = unifi.get_vouchers("default").await.unwrap();
+
+    assert_eq!(vouchers.len(), 1);
+    assert_eq!(vouchers[0].id, created[1].id);
+}
+
+#[tokio::test]
+async fn blocks_and_unblocks_devices() {
+    let mock = MockUnifi::start(UnifiPlatform::Legacy).await;
+    mock.add_device(MAC, true, false);
+    let unifi = UnifiController::new(&mock.config(UnifiPlatform::Legacy)).await;
+
+    unifi.block_device("default", MAC).await.unwrap();
+    assert_eq!(mock.device(MAC).unwrap()["blocked"], true);
+
+    unifi.unblock_device("default", MAC).await.unwrap();
+    assert_eq!(mock.device(MAC).unwrap()["blocked"], false);
+    assert_eq!(mock.commands_for(MAC), vec!["block-sta", "unblock-sta"]);
+}
@@ -0,0 +1,398 @@
+use std::{
+    net::{Ipv4Addr, TcpListener},
+    sync::{Arc, Mutex},
+};
+
+use super::{TestApp, smtp_sink::SmtpSink};
+use crate::{
+    configurations::config::{EmailVerificationConfig, SmsGatewayConfig, SmsVerificationConfig, SmsWebhookConfig},
+    model::{
+        entity::{
+            approver::{Approver, ApproverGroup},
+            client::{Client, ClientStatus},
+            code_delivery::CodeDelivery,
+            verification::{Verification, VerificationChannel},
+        },
+        repository::Repository,
+    },
+    security::portal_session::PortalSession,
+    utils::{
+        mailer::{Mailer, SmtpMailer},
+        sms::{SmsGateway, WebhookGateway, normalize_phone},
+    },
+};
+use bson::doc;
+use regex::Regex;
+use rocket::{
+    State,
+    http::Status,
+    post, routes,
+    serde::json::Json,
+    tokio::{
+        self,
+        time::{Duration, sleep},
+    },
+};
+use serde_json::{Value, json};
+
+type Received = Arc<Mutex<Vec<Value>>>;
+
+const MAC: &str = "4c:eb:42:9b:82:55";
+const EMAIL: &str = "jane@example.com";
+
+fn verification(sink: &SmtpSink) -> EmailVerificationConfig {
+    EmailVerificationConfig {
+        smtp: sink.config(),
+        code_size: 6,
+        code_minutes: 5,
+        subject: String::from("Your Wi-Fi code"),
+        body: String::from("Your code is {code}, valid for {minutes} minutes."),
+        max_per_address: 2,
+        max_per_mac: 3,
+        window_minutes: 60,
+    }
+}
+
+fn sms_verification() -> SmsVerificationConfig {
+    SmsVerificationConfig {
+        gateway: SmsGatewayConfig::Log,
+        code_size: 6,
+        code_minutes: 5,
+        message: String::from("Wi-Fi code {code}"),
+        max_per_phone: 2,
+        max_per_mac: 3,
+        window_minutes: 60,
+    }
+}
+
+fn submit_form<'c>(
+    app: &'c TestApp,
+    mac: &str,
+    phone: &str,
+    email: &str,
+    approver_code: Option<&str>,
+    code: Option<&str>,
+) -> rocket::local::asynchronous::LocalRequest<'c> {
+    let session = PortalSession::new(mac.into(), "default".into(), None, None, None);
+
+    app.client
+        .post("/api/client/connect?form")
+        .private_cookie(session.to_cookie())
+        .json(&json!({
+            "full_name": "Jane Doe",
+            "email": email,
+            "phone": phone,
+            "approver_code": approver_code,
+            "fields": {},
+            "verification_code": code,
+        }))
+}
+
+// Approver whose code is 12345678
+async fn save_approver(app: &TestApp) {
+    let approver = Approver {
+        id: String::new(),
+        username: String::from("reception"),
+        email: String::from("reception@example.com"),
+        password: String::new(),
+        secrete_code: String::from("12345678"),
+        validity: None,
+        group: ApproverGroup::DirectApproval,
+        sites: None,
+    };
+    app.repository::<Approver>().save(approver).await.unwrap();
+}
+
+#[post("/sms", data = "<body>")]
+fn sms_webhook(received: &State<Received>, body: Json<Value>) -> Status {
+    received.lock().unwrap().push(body.into_inner());
+    Status::Accepted
+}
+
+// Provider endpoint keeping the bodies it receives
+async fn start_webhook() -> (String, Received) {
+    let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .and_then(|l| l.local_addr())
+        .map(|a| a.port())
+        .unwrap();
+
+    let config = rocket::Config {
+        address: Ipv4Addr::LOCALHOST.into(),
+        port,
+        log_level: rocket::config::LogLevel::Off,
+        ..rocket::Config::debug_default()
+    };
+
+    let received = Received::default();
+    let rocket = rocket::custom(config).manage(received.clone()).mount("/", routes![sms_webhook]);
+    tokio::spawn(rocket.launch());
+
+    while tokio::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await.is_err() {
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    (format!("http://127.0.0.1:{port}/sms"), received)
+}
+
+#[tokio::test]
+async fn mails_through_the_smtp_relay() {
+    let sink = SmtpSink::start().await;
+    let mailer = SmtpMailer::new(&sink.config()).unwrap();
+
+    mailer.send("jane@example.com", "Hello", "Plain body").await.unwrap();
+
+    let messages = sink.messages();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("To: jane@example.com"));
+    assert!(messages[0].contains("Subject: Hello"));
+    assert!(messages[0].contains("Plain body"));
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn request_is_saved_once_the_email_code_is_confirmed() {
+    let mut app = TestApp::start().await;
+    app.config.clients.email_verification = Some(verification(&app.mail));
+    app.reload_config().await;
+
+    let phone = "5511999999999";
+
+    let res = submit_form(&app, MAC, phone, EMAIL, None, None).dispatch().await;
+    assert_eq!(res.status(), Status::Accepted);
+
+    // Pre-authorized to read the mail, nothing saved yet
+    assert_eq!(app.mock.commands_for(MAC), vec!["authorize-guest"]);
+    assert_eq!(app.mock.state().commands[0]["minutes"], 5);
+    assert!(app.repository::<Client>().find_one(doc! { "mac": MAC }).await.is_none());
+
+    let messages = app.mail.messages();
+    let code = Regex::new(r"code is (\d{6})").unwrap().captures(&messages[0]).unwrap()[1].to_string();
+
+    let res = submit_form(&app, MAC, phone, EMAIL, None, Some("000000")).dispatch().await;
+    assert_eq!(res.status(), Status::BadRequest);
+
+    let res = submit_form(&app, MAC, phone, EMAIL, None, Some(&code)).dispatch().await;
+    assert_eq!(res.status(), Status::Ok);
+
+    let client = app.repository::<Client>().find_one(doc! { "mac": MAC }).await.unwrap();
+    assert_eq!(client.status, ClientStatus::Pending);
+    assert!(app.repository::<Verification>().find_all().await.is_empty());
+
+    // The code is single use
+    let res = submit_form(&app, MAC, phone, EMAIL, None, Some(&code)).dispatch().await;
+    assert_eq!(res.status(), Status::BadRequest);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn new_email_code_does_not_extend_the_access() {
+    let mut app = TestApp::start().await;
+    app.config.clients.email_verification = Some(verification(&app.mail));
+    app.reload_config().await;
+
+    // A code sent two minutes ago, past the resend wait
+    let mut previous = Verification::new(VerificationChannel::Email, MAC, "default", EMAIL, String::from("x"), 5);
+    previous.created_at -= chrono::Duration::minutes(2);
+    app.repository::<Verification>().save(previous).await.unwrap();
+
+    let res = submit_form(&app, MAC, "5511999999999", EMAIL, None, None).dispatch().await;
+    assert_eq!(res.status(), Status::Accepted);
+
+    assert_eq!(app.mail.messages().len(), 1);
+    assert!(app.mock.commands_for(MAC).is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn email_code_is_not_sent_without_an_approval() {
+    let mut app = TestApp::start().await;
+    app.config.clients.free_request = false;
+    app.config.clients.email_verification = Some(verification(&app.mail));
+    app.reload_config().await;
+
+    let res = submit_form(&app, MAC, "5511999999999", EMAIL, None, None).dispatch().await;
+    assert_eq!(res.status(), Status::BadRequest);
+
+    assert!(app.mock.commands_for(MAC).is_empty());
+    assert!(app.mail.messages().is_empty());
+    assert!(app.repository::<Verification>().find_all().await.is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn email_code_is_not_sent_for_a_wrong_approver_code() {
+    let mut app = TestApp::start().await;
+    app.config.clients.email_verification = Some(verification(&app.mail));
+    app.reload_config().await;
+
+    save_approver(&app).await;
+
+    let res = submit_form(&app, MAC, "5511999999999", EMAIL, Some("87654321"), None).dispatch().await;
+    assert_eq!(res.status(), Status::BadRequest);
+
+    assert!(app.mock.commands_for(MAC).is_empty());
+    assert!(app.mail.messages().is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn email_codes_are_limited_per_address_and_per_device() {
+    let mut app = TestApp::start().await;
+    app.config.clients.email_verification = Some(verification(&app.mail));
+    app.reload_config().await;
+
+    let phone = "5511999999999";
+    let deliveries = app.repository::<CodeDelivery>();
+    let delivery = |mac: &str, email: &str| CodeDelivery::new(VerificationChannel::Email, mac, "default", email, 60);
+
+    // Two mails to the address from other devices use its whole allowance, whatever the case
+    for mac in ["70:a7:41:dd:7a:01", "70:a7:41:dd:7a:02"] {
+        deliveries.save(delivery(mac, EMAIL)).await.unwrap();
+    }
+
+    let res = submit_form(&app, MAC, phone, "Jane@Example.com", None, None).dispatch().await;
+    assert_eq!(res.status(), Status::TooManyRequests);
+
+    // Three mails from the device use its allowance, whatever the address
+    for other in ["a@example.com", "b@example.com", "c@example.com"] {
+        deliveries.save(delivery(MAC, other)).await.unwrap();
+    }
+
+    let res = submit_form(&app, MAC, phone, "john@example.com", None, None).dispatch().await;
+    assert_eq!(res.status(), Status::TooManyRequests);
+
+    let res = submit_form(&app, "70:a7:41:dd:7a:03", phone, "john@example.com", None, None).dispatch().await;
+    assert_eq!(res.status(), Status::Accepted);
+    assert_eq!(app.mail.messages().len(), 1);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn webhook_gateway_posts_the_text() {
+    let (url, received) = start_webhook().await;
+
+    let gateway = WebhookGateway::new(SmsWebhookConfig {
+        url,
+        headers: Some([(String::from("Authorization"), String::from("Bearer token"))].into()),
+        to_field: String::from("number"),
+        message_field: String::from("text"),
+        extra: Some(json!({ "sender": "WIFI" }).as_object().unwrap().clone()),
+    });
+
+    gateway.send("+5511999999999", "Wi-Fi code 123456").await.unwrap();
+
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received, vec![json!({ "number": "+5511999999999", "text": "Wi-Fi code 123456", "sender": "WIFI" })]);
+
+    assert_eq!(normalize_phone(" +55 (11) 99999-9999 "), "+5511999999999");
+    assert_eq!(normalize_phone("11 99999-9999"), "11999999999");
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn request_is_saved_once_the_sms_code_is_confirmed() {
+    let mut app = TestApp::start().await;
+    app.config.clients.sms_verification = Some(sms_verification());
+    app.reload_config().await;
+
+    let res = submit_form(&app, MAC, "+55 (11) 99999-9999", EMAIL, None, None).dispatch().await;
+    assert_eq!(res.status(), Status::Accepted);
+
+    // The text needs no Wi-Fi, the device stays off until the code is confirmed
+    assert!(app.mock.commands_for(MAC).is_empty());
+
+    let sent = app.sms.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, "+5511999999999");
+    let code = sent[0].1.trim_start_matches("Wi-Fi code ").to_string();
+
+    let res = submit_form(&app, MAC, "+5511999999999", EMAIL, None, Some("000000")).dispatch().await;
+    assert_eq!(res.status(), Status::BadRequest);
+
+    let res = submit_form(&app, MAC, "+5511999999999", EMAIL, None, Some(&code)).dispatch().await;
+    assert_eq!(res.status(), Status::Ok);
+
+    let client = app.repository::<Client>().find_one(doc! { "mac": MAC }).await.unwrap();
+    assert_eq!(client.status, ClientStatus::Pending);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn sms_codes_are_limited_per_phone_and_per_device() {
+    let mut app = TestApp::start().await;
+    app.config.clients.sms_verification = Some(sms_verification());
+    app.reload_config().await;
+
+    let phone = "+5511999999999";
+    let deliveries = app.repository::<CodeDelivery>();
+    let delivery = |mac: &str, phone: &str| CodeDelivery::new(VerificationChannel::Sms, mac, "default", phone, 60);
+
+    // Two texts to the phone from other devices use its whole allowance
+    for mac in ["70:a7:41:dd:7a:01", "70:a7:41:dd:7a:02"] {
+        deliveries.save(delivery(mac, phone)).await.unwrap();
+    }
+
+    let res = submit_form(&app, MAC, phone, EMAIL, None, None).dispatch().await;
+    assert_eq!(res.status(), Status::TooManyRequests);
+
+    // Three texts from the device use its allowance, whatever the phone
+    for other in ["+5511888888881", "+5511888888882", "+5511888888883"] {
+        deliveries.save(delivery(MAC, other)).await.unwrap();
+    }
+
+    let res = submit_form(&app, MAC, "+5511777777777", EMAIL, None, None).dispatch().await;
+    assert_eq!(res.status(), Status::TooManyRequests);
+
+    let res = submit_form(&app, "70:a7:41:dd:7a:03", "+5511777777777", EMAIL, None, None).dispatch().await;
+    assert_eq!(res.status(), Status::Accepted);
+    assert_eq!(app.sms.sent().len(), 1);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn sms_code_is_not_sent_without_an_approval() {
+    let mut app = TestApp::start().await;
+    app.config.clients.free_request = false;
+    app.config.clients.sms_verification = Some(sms_verification());
+    app.reload_config().await;
+
+    let res = submit_form(&app, MAC, "+5511999999999", EMAIL, None, None).dispatch().await;
+    assert_eq!(res.status(), Status::BadRequest);
+
+    assert!(app.mock.commands_for(MAC).is_empty());
+    assert!(app.sms.sent().is_empty());
+    assert!(app.repository::<Verification>().find_all().await.is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+#[ignore = "needs MongoDB"]
+async fn sms_code_is_not_sent_for_a_wrong_approver_code() {
+    let mut app = TestApp::start().await;
+    app.config.clients.sms_verification = Some(sms_verification());
+    app.reload_config().await;
+
+    save_approver(&app).await;
+
+    let res = submit_form(&app, MAC, "+5511999999999", EMAIL, Some("87654321"), None).dispatch().await;
+    assert_eq!(res.status(), Status::BadRequest);
+
+    assert!(app.mock.commands_for(MAC).is_empty());
+    assert!(app.sms.sent().is_empty());
+
+    app.cleanup().await;
+}
@@ -0,0 +1,60 @@
+use std::fmt::{self, Display};
+
+use crate::utils::error::{CustomError, Error};
+
+// Enums
+#[derive(Debug)]
+pub enum UnifiError {
+    /// The controller could not be reached (connection, TLS, timeout)
+    Transport(reqwest::Error),
+    /// The controller rejected the credentials or the session
+    Auth(String),
+    /// The controller answered with `meta.rc = "error"`
+    Api(String),
+    /// The controller answered with something we could not read
+    Decode(String),
+    /// The events websocket could not be opened or was dropped
+    Socket(String),
+}
+
+// Impls
+impl Display for UnifiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnifiError::Transport(e) => write!(f, "UniFi controller unreachable: {e}"),
+            UnifiError::Auth(msg) => write!(f, "UniFi authentication failed: {msg}"),
+            UnifiError::Api(msg) => write!(f, "UniFi controller refused the request: {msg}"),
+            UnifiError::Decode(msg) => write!(f, "Invalid response from UniFi controller: {msg}"),
+            UnifiError::Socket(msg) => write!(f, "UniFi events stream failed: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for UnifiError {}
+
+impl From<reqwest::Error> for UnifiError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_decode() {
+            return UnifiError::Decode(err.to_string());
+        }
+
+        UnifiError::Transport(err)
+    }
+}
+
+impl From<serde_json::Error> for UnifiError {
+    fn from(err: serde_json::Error) -> Self {
+        UnifiError::Decode(err.to_string())
+    }
+}
+
+impl From<UnifiError> for CustomError {
+    fn from(err: UnifiError) -> Self {
+        match err {
+            UnifiError::Transport(_) | UnifiError::Socket(_) => {
+                Error::new_service_unavailable(&err.to_string())
+            }
+            _ => Error::new_bad_gateway(&err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use rocket::futures::StreamExt;
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    Connector, MaybeTlsStream, WebSocketStream, connect_async_tls_with_config,
+    tungstenite::{self, Message, handshake::client::Request},
+};
+
+use super::error::UnifiError;
+
+// Enums
+/// Guest events of the controller we keep the clients in sync with, keyed by the device MAC
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnifiEvent {
+    Connected(String),
+    Disconnected(String),
+    Authorized(String),
+    Expired(String),
+}
+
+// Structs
+pub struct UnifiEventStream {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+// Impls
+impl UnifiEvent {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let key = value.get("key")?.as_str()?;
+        let mac = value
+            .get("guest")
+            .or_else(|| value.get("user"))
+            .and_then(|m| m.as_str())?
+            .to_string();
+
+        match key {
+            "EVT_WG_Connected" => Some(UnifiEvent::Connected(mac)),
+            "EVT_WG_Disconnected" => Some(UnifiEvent::Disconnected(mac)),
+            k if k.starts_with("EVT_WG_AuthorizationEnded") => Some(UnifiEvent::Expired(mac)),
+            k if k.starts_with("EVT_WG_AuthorizedBy") => Some(UnifiEvent::Authorized(mac)),
+            _ => None,
+        }
+    }
+
+    pub fn mac(&self) -> &str {
+        match self {
+            UnifiEvent::Connected(mac)
+            | UnifiEvent::Disconnected(mac)
+            | UnifiEvent::Authorized(mac)
+            | UnifiEvent::Expired(mac) => mac,
+        }
+    }
+}
+
+impl UnifiEventStream {
+    pub async fn connect(request: Request) -> Result<Self, UnifiError> {
+        let tls = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(|e| UnifiError::Socket(e.to_string()))?;
+
+        let res = connect_async_tls_with_config(request, None, false, Some(Connector::NativeTls(tls))).await;
+
+        match res {
+            Ok((socket, _)) => Ok(Self { socket }),
+
+            Err(tungstenite::Error::Http(response))
+                if response.status() == 401 || response.status() == 403 =>
+            {
+                Err(UnifiError::Auth(format!("session rejected ({})", response.status())))
+            }
+
+            Err(e) => Err(UnifiError::Socket(e.to_string())),
+        }
+    }
+
+    /// Waits for the next batch of guest events, `None` once the controller closes the stream
+    pub async fn next(&mut self) -> Option<Result<Vec<UnifiEvent>, UnifiError>> {
+        loop {
+            let message = match self.socket.next().await? {
+                Ok(m) => m,
+                Err(e) => return Some(Err(UnifiError::Socket(e.to_string()))),
+            };
+
+            match message {
+                Message::Text(text) => {
+                    let value: Value = match serde_json::from_str(text.as_str()) {
+                        Ok(v) => v,
+                        Err(e) => return Some(Err(e.into())),
+                    };
+
+                    if value["meta"]["message"] != "events" {
+                        continue;
+                    }
+
+                    let events = value["data"]
+                        .as_array()
+                        .map(|data| data.iter().filter_map(UnifiEvent::from_value).collect())
+                        .unwrap_or_default();
+
+                    return Some(Ok(events));
+                }
+
+                Message::Close(_) => return None,
+
+                _ => continue,
+            }
+        }
+    }
+}
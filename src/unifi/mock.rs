@@ -0,0 +1,534 @@
+use rocket::http::{CookieJar, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::{Responder, State, get, post, put, routes};
+use serde_json::{Value, json};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::time::{Duration, sleep};
+
+use super::unifi::UnifiPlatform;
+use crate::configurations::config::UnifiConfig;
+
+// Types
+type MockResponse = Custom<Json<Value>>;
+type MockHandle = Arc<Mutex<MockState>>;
+
+// Structs
+/// State of the fake controller, tests read and change it between requests
+pub struct MockState {
+    pub platform: UnifiPlatform,
+    pub session: Option<String>,
+    pub csrf_token: String,
+    pub logins: usize,
+    pub refuse_login: bool,
+    pub refuse_commands: bool,
+    pub sites: Vec<String>,
+    pub devices: Vec<Value>,
+    pub network_devices: Vec<Value>,
+    pub guest_access: Value,
+    pub wlans: Vec<Value>,
+    pub user_groups: Vec<Value>,
+    pub vouchers: Vec<Value>,
+    pub commands: Vec<Value>,
+    pub renames: Vec<(String, String)>,
+}
+
+/// In-process UniFi controller answering the calls made by `UnifiController`
+#[derive(Clone)]
+pub struct MockUnifi {
+    pub url: String,
+    state: MockHandle,
+}
+
+#[derive(Responder)]
+struct MockLogin {
+    inner: MockResponse,
+    csrf: Header<'static>,
+}
+
+struct MockSession {
+    cookie: Option<String>,
+    csrf: Option<String>,
+}
+
+// Impls
+impl MockUnifi {
+    pub async fn start(platform: UnifiPlatform) -> Self {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|l| l.local_addr())
+            .map(|a| a.port())
+            .expect("No free port for the mock controller");
+
+        let state = Arc::new(Mutex::new(MockState {
+            platform,
+            session: None,
+            csrf_token: String::from("mock-csrf"),
+            logins: 0,
+            refuse_login: false,
+            refuse_commands: false,
+            sites: vec![String::from("default")],
+            devices: vec![],
+            network_devices: vec![],
+            guest_access: json!({
+                "_id": "setting-guest-access",
+                "key": "guest_access",
+                "auth": "hotspot",
+                "expire": 480,
+                "allowed_subnet_1": "10.0.0.0/24",
+                "allowed_subnet_2": "portal.example.com",
+                "allowed_subnet_3": "192.168.50.0/24",
+            }),
+            wlans: vec![json!({
+                "_id": "wlan-guest",
+                "name": "Guests",
+                "enabled": true,
+                "security": "wpapsk",
+                "is_guest": true,
+                "x_passphrase": "first-passphrase",
+            })],
+            user_groups: vec![
+                json!({ "_id": "group-default", "name": "Default", "qos_rate_max_down": -1, "qos_rate_max_up": -1 }),
+                json!({ "_id": "group-staff", "name": "Staff Guests", "qos_rate_max_down": 20480, "qos_rate_max_up": 10240 }),
+            ],
+            vouchers: vec![],
+            commands: vec![],
+            renames: vec![],
+        }));
+
+        let config = rocket::Config {
+            address: Ipv4Addr::LOCALHOST.into(),
+            port,
+            log_level: rocket::config::LogLevel::Off,
+            ..rocket::Config::debug_default()
+        };
+
+        let api = routes![login, self_sites, stat_guest, stat_sta, stat_device, cmd_stamgr, upd_user, get_guest_access, set_guest_access, get_wlans, set_wlan, get_user_groups, cmd_hotspot, get_vouchers, find_vouchers];
+        let rocket = rocket::custom(config)
+            .manage(state.clone())
+            .mount("/", routes![root])
+            .mount("/api", api.clone())
+            .mount("/api", routes![login_unifi_os])
+            .mount("/proxy/network/api", api);
+
+        tokio::spawn(rocket.launch());
+
+        while tokio::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await.is_err() {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        let url = match platform {
+            UnifiPlatform::UnifiOs => format!("http://127.0.0.1:{port}"),
+            _ => format!("http://127.0.0.1:{port}/api"),
+        };
+
+        Self { url, state }
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn config(&self, platform: UnifiPlatform) -> UnifiConfig {
+        UnifiConfig {
+            name: String::from("mock"),
+            sites: None,
+            url: self.url.clone(),
+            username: String::from("admin"),
+            password: String::from("admin"),
+            platform: Some(platform),
+        }
+    }
+
+    /// Drops the current session, like a controller restart would
+    pub fn expire_session(&self) {
+        self.state().session = None;
+    }
+
+    pub fn add_device(&self, mac: &str, is_guest: bool, expired: bool) {
+        let mut state = self.state();
+        let id = format!("user-{}", state.devices.len());
+
+        state.devices.push(json!({
+            "_id": id,
+            "mac": mac,
+            "is_guest": is_guest,
+            "expired": expired,
+        }));
+    }
+
+    pub fn add_network_device(&self, mac: &str, kind: &str, guests: u32) {
+        let mut state = self.state();
+        let id = format!("device-{}", state.network_devices.len());
+
+        state.network_devices.push(json!({
+            "_id": id,
+            "mac": mac,
+            "name": format!("{kind}-{mac}"),
+            "model": "U7PG2",
+            "type": kind,
+            "state": 1,
+            "guest-num_sta": guests,
+            "num_sta": guests,
+            "uptime": 3600,
+        }));
+    }
+
+    pub fn add_voucher(&self, code: &str, duration: u32, quota: u32) -> String {
+        let mut state = self.state();
+        let id = format!("voucher-{}", state.vouchers.len());
+
+        state.vouchers.push(json!({
+            "_id": id,
+            "code": code,
+            "create_time": 1,
+            "duration": duration,
+            "quota": quota,
+            "used": 0,
+            "status": "VALID_ONE",
+        }));
+
+        id
+    }
+
+    pub fn voucher(&self, id: &str) -> Option<Value> {
+        self.state().vouchers.iter().find(|v| v["_id"] == id).cloned()
+    }
+
+    pub fn device(&self, mac: &str) -> Option<Value> {
+        self.state().devices.iter().find(|d| d["mac"] == mac).cloned()
+    }
+
+    pub fn commands_for(&self, mac: &str) -> Vec<String> {
+        self.state()
+            .commands
+            .iter()
+            .filter(|c| c["mac"] == mac)
+            .filter_map(|c| c["cmd"].as_str().map(String::from))
+            .collect()
+    }
+}
+
+impl MockState {
+    fn check(&self, session: &MockSession, write: bool) -> Option<MockResponse> {
+        if self.session.is_none() || session.cookie != self.session {
+            return Some(error(Status::Unauthorized, "api.err.LoginRequired"));
+        }
+
+        if write && self.platform == UnifiPlatform::UnifiOs && session.csrf.as_deref() != Some(self.csrf_token.as_str()) {
+            return Some(error(Status::Forbidden, "api.err.InvalidCsrfToken"));
+        }
+
+        None
+    }
+
+    fn apply_command(&mut self, command: &Value) {
+        let mac = command["mac"].as_str().unwrap_or_default().to_string();
+        let next_id = format!("user-{}", self.devices.len());
+
+        let device = match self.devices.iter_mut().find(|d| d["mac"] == mac.as_str()) {
+            Some(d) => d,
+            None => {
+                self.devices.push(json!({ "_id": next_id, "mac": mac, "is_guest": true, "expired": true }));
+                self.devices.last_mut().unwrap()
+            }
+        };
+
+        match command["cmd"].as_str() {
+            Some("authorize-guest") => {
+                device["expired"] = json!(false);
+                device["authorized"] = json!(true);
+                device["minutes"] = command["minutes"].clone();
+            }
+
+            Some("unauthorize-guest") => {
+                device["expired"] = json!(true);
+                device["authorized"] = json!(false);
+            }
+
+            Some("block-sta") => device["blocked"] = json!(true),
+            Some("unblock-sta") => device["blocked"] = json!(false),
+            _ => {}
+        }
+    }
+}
+
+// Guards
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MockSession {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(MockSession {
+            cookie: request.cookies().get("unifises").map(|c| c.value().to_string()),
+            csrf: request.headers().get_one("X-CSRF-Token").map(String::from),
+        })
+    }
+}
+
+// Functions
+fn ok(data: Value) -> MockResponse {
+    Custom(Status::Ok, Json(json!({ "meta": { "rc": "ok" }, "data": data })))
+}
+
+fn error(status: Status, msg: &str) -> MockResponse {
+    Custom(status, Json(json!({ "meta": { "rc": "error", "msg": msg }, "data": [] })))
+}
+
+// ENDPOINTS
+#[get("/")]
+fn root(state: &State<MockHandle>) -> Status {
+    // The legacy controller redirects to /manage, only the status is looked at
+    match state.lock().unwrap().platform {
+        UnifiPlatform::UnifiOs => Status::Ok,
+        _ => Status::Found,
+    }
+}
+
+fn open_session(state: &State<MockHandle>, cookies: &CookieJar<'_>) -> MockLogin {
+    let mut state = state.lock().unwrap();
+
+    if state.refuse_login {
+        return MockLogin {
+            inner: error(Status::BadRequest, "api.err.Invalid"),
+            csrf: Header::new("X-CSRF-Token", ""),
+        };
+    }
+
+    state.logins += 1;
+    let token = format!("session-{}", state.logins);
+    state.session = Some(token.clone());
+    cookies.add(("unifises", token));
+
+    MockLogin {
+        inner: ok(json!([])),
+        csrf: Header::new("X-CSRF-Token", state.csrf_token.clone()),
+    }
+}
+
+#[post("/login")]
+fn login(state: &State<MockHandle>, cookies: &CookieJar<'_>) -> MockLogin {
+    open_session(state, cookies)
+}
+
+#[post("/auth/login")]
+fn login_unifi_os(state: &State<MockHandle>, cookies: &CookieJar<'_>) -> MockLogin {
+    open_session(state, cookies)
+}
+
+#[get("/self/sites")]
+fn self_sites(state: &State<MockHandle>, session: MockSession) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    let sites: Vec<Value> = state
+        .sites
+        .iter()
+        .map(|s| json!({ "_id": format!("site-{s}"), "name": s, "desc": s }))
+        .collect();
+
+    ok(json!(sites))
+}
+
+#[get("/s/<_site>/stat/guest")]
+fn stat_guest(_site: &str, state: &State<MockHandle>, session: MockSession) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    let guests: Vec<Value> = state.devices.iter().filter(|d| d["is_guest"] == true).cloned().collect();
+    ok(json!(guests))
+}
+
+#[get("/s/<_site>/stat/sta")]
+fn stat_sta(_site: &str, state: &State<MockHandle>, session: MockSession) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    ok(json!(state.devices))
+}
+
+#[get("/s/<_site>/stat/device")]
+fn stat_device(_site: &str, state: &State<MockHandle>, session: MockSession) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    ok(json!(state.network_devices))
+}
+
+#[post("/s/<_site>/cmd/stamgr", data = "<body>")]
+fn cmd_stamgr(_site: &str, state: &State<MockHandle>, session: MockSession, body: Json<Value>) -> MockResponse {
+    let mut state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, true) {
+        return err;
+    }
+
+    if state.refuse_commands {
+        return error(Status::BadRequest, "api.err.UnknownStation");
+    }
+
+    let command = body.into_inner();
+    state.apply_command(&command);
+    state.commands.push(command);
+
+    ok(json!([]))
+}
+
+#[put("/s/<_site>/upd/user/<id>", data = "<body>")]
+fn upd_user(_site: &str, state: &State<MockHandle>, session: MockSession, id: String, body: Json<Value>) -> MockResponse {
+    let mut state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, true) {
+        return err;
+    }
+
+    if let Some(name) = body["name"].as_str() {
+        state.renames.push((id.clone(), name.to_string()));
+    }
+
+    if let Some(device) = state.devices.iter_mut().find(|d| d["_id"] == id.as_str())
+        && let Some(group) = body.get("usergroup_id")
+    {
+        device["usergroup_id"] = group.clone();
+    }
+
+    ok(json!([]))
+}
+
+#[get("/s/<_site>/rest/setting/guest_access")]
+fn get_guest_access(_site: &str, state: &State<MockHandle>, session: MockSession) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    ok(json!([state.guest_access]))
+}
+
+#[put("/s/<_site>/rest/setting/guest_access/<_id>", data = "<body>")]
+fn set_guest_access(_site: &str, _id: &str, state: &State<MockHandle>, session: MockSession, body: Json<Value>) -> MockResponse {
+    let mut state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, true) {
+        return err;
+    }
+
+    state.guest_access = body.into_inner();
+    ok(json!([state.guest_access]))
+}
+
+#[get("/s/<_site>/rest/wlanconf")]
+fn get_wlans(_site: &str, state: &State<MockHandle>, session: MockSession) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    ok(json!(state.wlans))
+}
+
+#[put("/s/<_site>/rest/wlanconf/<id>", data = "<body>")]
+fn set_wlan(_site: &str, id: &str, state: &State<MockHandle>, session: MockSession, body: Json<Value>) -> MockResponse {
+    let mut state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, true) {
+        return err;
+    }
+
+    let Some(wlan) = state.wlans.iter_mut().find(|w| w["_id"] == id) else {
+        return error(Status::BadRequest, "api.err.IdInvalid");
+    };
+
+    if let Some(values) = body.as_object() {
+        for (key, value) in values {
+            wlan[key] = value.clone();
+        }
+    }
+
+    ok(json!([wlan]))
+}
+
+#[get("/s/<_site>/rest/usergroup")]
+fn get_user_groups(_site: &str, state: &State<MockHandle>, session: MockSession) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    ok(json!(state.user_groups))
+}
+
+#[post("/s/<_site>/cmd/hotspot", data = "<body>")]
+fn cmd_hotspot(_site: &str, state: &State<MockHandle>, session: MockSession, body: Json<Value>) -> MockResponse {
+    let mut state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, true) {
+        return err;
+    }
+
+    let command = body.into_inner();
+    state.commands.push(command.clone());
+
+    match command["cmd"].as_str() {
+        Some("create-voucher") => {
+            let create_time = state.commands.len() as u64 + 1000;
+
+            for _ in 0..command["n"].as_u64().unwrap_or(1) {
+                let index = state.vouchers.len();
+                state.vouchers.push(json!({
+                    "_id": format!("voucher-{index}"),
+                    "code": format!("{:010}", 1234500000 + index),
+                    "create_time": create_time,
+                    "duration": command["expire"],
+                    "quota": command["quota"],
+                    "used": 0,
+                    "note": command["note"],
+                    "status": "VALID_ONE",
+                    "qos_rate_max_up": command["up"],
+                    "qos_rate_max_down": command["down"],
+                    "qos_usage_quota": command["bytes"],
+                }));
+            }
+
+            ok(json!([{ "create_time": create_time }]))
+        }
+
+        Some("delete-voucher") => {
+            let id = command["_id"].clone();
+            state.vouchers.retain(|v| v["_id"] != id);
+            ok(json!([]))
+        }
+
+        _ => error(Status::BadRequest, "api.err.InvalidCommand"),
+    }
+}
+
+#[get("/s/<_site>/stat/voucher")]
+fn get_vouchers(_site: &str, state: &State<MockHandle>, session: MockSession) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    ok(json!(state.vouchers))
+}
+
+#[post("/s/<_site>/stat/voucher", data = "<body>")]
+fn find_vouchers(_site: &str, state: &State<MockHandle>, session: MockSession, body: Json<Value>) -> MockResponse {
+    let state = state.lock().unwrap();
+    if let Some(err) = state.check(&session, false) {
+        return err;
+    }
+
+    let vouchers: Vec<&Value> = state
+        .vouchers
+        .iter()
+        .filter(|v| v["create_time"] == body["create_time"])
+        .collect();
+
+    ok(json!(vouchers))
+}
@@ -1 +1,5 @@
+pub mod error;
+pub mod events;
+#[cfg(test)]
+pub mod mock;
 pub mod unifi;
@@ -1,25 +1,65 @@
+use reqwest::cookie::{CookieStore, Jar};
 use rocket::State;
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket::serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
 use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 
-use crate::model::entity::client::Client;
+use super::error::UnifiError;
+use super::events::UnifiEventStream;
+use crate::configurations::config::UnifiConfig;
+use crate::model::entity::client::{Client, ClientLimits};
 
 // Types
-pub type UnifiState = State<Arc<Mutex<UnifiController>>>;
+pub type UnifiState = State<UnifiControllers>;
+
+// Enums
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum UnifiPlatform {
+    /// Detects the platform on the first authentication
+    #[default]
+    Auto,
+    /// Classic Network Application (software controller / Cloud Key Gen1)
+    Legacy,
+    /// UniFi OS consoles (UDM, UDR, Cloud Key Gen2+)
+    UnifiOs,
+}
 
 // Structs
+/// Handle to a controller, every clone shares the same cookies and login session
 #[derive(Clone)]
 pub struct UnifiController {
+    name: String,
+    sites: Vec<String>,
     base_url: String,
     username: String,
     password: String,
+    cookies: Arc<Jar>,
     client: reqwest::Client,
+    session: Arc<RwLock<UnifiSession>>,
+    login_lock: Arc<Mutex<()>>,
+}
+
+#[derive(Default)]
+struct UnifiSession {
+    platform: UnifiPlatform,
+    csrf_token: Option<String>,
     authentication_time: Option<Instant>,
+    /// Incremented on every successful login
+    generation: u64,
+}
+
+/// Every configured controller, guests are routed to the one they were found on
+#[derive(Clone)]
+pub struct UnifiControllers {
+    controllers: Arc<[UnifiController]>,
+    /// Controllers on which each site name was last seen
+    site_owners: Arc<RwLock<HashMap<String, Vec<String>>>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -27,6 +67,12 @@ pub struct DeviceAuthorization {
     cmd: String,
     mac: Option<String>,
     minutes: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    up: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    down: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,6 +87,162 @@ pub struct DisconnectDevice {
     mac: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct BlockDevice {
+    cmd: String,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VoucherCreation {
+    cmd: String,
+    n: u32,
+    expire: u32,
+    expire_number: u32,
+    expire_unit: u32,
+    quota: u32,
+    note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    up: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    down: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VoucherRevoke {
+    cmd: String,
+    #[serde(rename = "_id")]
+    id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Voucher {
+    #[serde(rename = "_id")]
+    pub id: String,
+
+    /// Code printed for the guest, without the dash
+    pub code: String,
+
+    pub create_time: Option<u64>,
+
+    /// Duration of the authorization in minutes
+    pub duration: Option<u32>,
+
+    /// 0 for unlimited uses, otherwise the number of uses allowed
+    pub quota: Option<u32>,
+
+    pub used: Option<u32>,
+
+    pub note: Option<String>,
+
+    pub status: Option<String>,
+
+    pub qos_rate_max_up: Option<u32>,
+
+    pub qos_rate_max_down: Option<u32>,
+
+    pub qos_usage_quota: Option<u32>,
+}
+
+/// Network device adopted by the controller, read from `stat/device`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccessPoint {
+    #[serde(rename = "_id")]
+    pub id: String,
+
+    pub mac: String,
+
+    pub name: Option<String>,
+
+    /// Hardware model code (ex.: "U7PG2")
+    pub model: Option<String>,
+
+    /// Device type: "uap" for access points, "usw" for switches, "ugw"/"udm" for gateways
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+
+    /// 1 when connected to the controller, 0 when disconnected, other values while provisioning or upgrading
+    pub state: Option<u8>,
+
+    pub ip: Option<String>,
+
+    pub version: Option<String>,
+
+    /// Guests currently associated to this device
+    #[serde(rename = "guest-num_sta")]
+    pub guests: Option<u32>,
+
+    /// Every station currently associated to this device
+    pub num_sta: Option<u32>,
+
+    /// Seconds since the device booted
+    pub uptime: Option<u64>,
+}
+
+/// Rate profile of the guests, read from `rest/usergroup`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserGroup {
+    #[serde(rename = "_id")]
+    pub id: String,
+
+    pub name: String,
+
+    /// Kbps, -1 for unlimited
+    pub qos_rate_max_down: Option<i64>,
+
+    /// Kbps, -1 for unlimited
+    pub qos_rate_max_up: Option<i64>,
+}
+
+/// WLAN of a site, read from `rest/wlanconf`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Wlan {
+    #[serde(rename = "_id")]
+    pub id: String,
+
+    /// SSID broadcast by the access points
+    pub name: String,
+
+    pub enabled: Option<bool>,
+
+    /// "open", "wpapsk", "wpaeap"...
+    pub security: Option<String>,
+
+    pub is_guest: Option<bool>,
+
+    pub x_passphrase: Option<String>,
+}
+
+/// `guest_access` setting document of a site. Every key is kept, writing it back does not drop the ones we do not model
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GuestAccessSetting {
+    #[serde(rename = "_id")]
+    pub id: String,
+
+    #[serde(flatten)]
+    pub values: serde_json::Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Site {
+    #[serde(rename = "_id")]
+    pub id: String,
+
+    /// Short name used in the API paths (ex.: "default")
+    pub name: String,
+
+    /// Name shown in the UniFi interface
+    pub desc: Option<String>,
+
+    pub role: Option<String>,
+
+    /// Name of the configured controller that owns this site
+    #[serde(skip_deserializing)]
+    pub controller: String,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DeviceInfo {
     /// ID interno do UniFi para este registro
@@ -106,19 +308,29 @@ pub struct DeviceInfo {
     pub rx_bytes: Option<usize>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct ApiMeta {
+    rc: String,
+    msg: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ApiResponse {
-    meta: Value,
+    meta: ApiMeta,
+    #[serde(default)]
     data: Value,
 }
 
 // Impls
 impl DeviceAuthorization {
-    pub fn new(mac: String, minutes: u16) -> Self {
+    pub fn new(mac: String, minutes: u16, limits: &ClientLimits) -> Self {
         Self {
             cmd: String::from("authorize-guest"),
             mac: Some(mac),
             minutes: Some(minutes),
+            up: limits.up,
+            down: limits.down,
+            bytes: limits.bytes,
         }
     }
 }
@@ -141,40 +353,336 @@ impl DisconnectDevice {
     }
 }
 
+impl BlockDevice {
+    pub fn new_block(mac: String) -> Self {
+        Self {
+            cmd: String::from("block-sta"),
+            mac,
+        }
+    }
+
+    pub fn new_unblock(mac: String) -> Self {
+        Self {
+            cmd: String::from("unblock-sta"),
+            mac,
+        }
+    }
+}
+
+impl VoucherCreation {
+    pub fn new(count: u32, minutes: u32, quota: u32, note: Option<String>, limits: &ClientLimits) -> Self {
+        Self {
+            cmd: String::from("create-voucher"),
+            n: count,
+            expire: minutes,
+            expire_number: minutes,
+            expire_unit: 1,
+            quota,
+            note,
+            up: limits.up,
+            down: limits.down,
+            bytes: limits.bytes,
+        }
+    }
+}
+
+impl VoucherRevoke {
+    pub fn new(id: String) -> Self {
+        Self {
+            cmd: String::from("delete-voucher"),
+            id,
+        }
+    }
+}
+
+impl Voucher {
+    /// Limits the controller applies to guests using this voucher
+    pub fn limits(&self) -> ClientLimits {
+        ClientLimits {
+            up: self.qos_rate_max_up,
+            down: self.qos_rate_max_down,
+            bytes: self.qos_usage_quota,
+        }
+    }
+}
+
+impl ApiResponse {
+    pub fn into_result(self) -> Result<Value, UnifiError> {
+        if self.meta.rc == "ok" {
+            return Ok(self.data);
+        }
+
+        let msg = self.meta.msg.unwrap_or(self.meta.rc);
+        if msg == "api.err.LoginRequired" {
+            return Err(UnifiError::Auth(msg));
+        }
+
+        Err(UnifiError::Api(msg))
+    }
+}
+
+impl UnifiControllers {
+    pub async fn new(configs: &[UnifiConfig]) -> Self {
+        let mut controllers = vec![];
+        for config in configs {
+            controllers.push(UnifiController::new(config).await);
+        }
+
+        if controllers.is_empty() {
+            panic!("At least one UniFi controller must be configured");
+        }
+
+        Self {
+            controllers: controllers.into(),
+            site_owners: Arc::default(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UnifiController> {
+        self.controllers.iter().find(|c| c.name == name)
+    }
+
+    /// Controller mapped to the site, or the only one that has it. A site name found on several
+    /// controllers without a mapping is ambiguous, the first one is used (`record_sites` warns about it)
+    pub fn for_site(&self, site: &str) -> &UnifiController {
+        if let Some(controller) = self.controllers.iter().find(|c| c.serves(site)) {
+            return controller;
+        }
+
+        let owners = self.site_owners.read().unwrap().get(site).cloned().unwrap_or_default();
+        owners
+            .first()
+            .and_then(|name| self.get(name))
+            .unwrap_or(&self.controllers[0])
+    }
+
+    /// Controller recorded for a guest, by site only for the records written before it was kept
+    pub fn route(&self, controller: Option<&str>, site: &str) -> &UnifiController {
+        match controller.and_then(|name| self.get(name)) {
+            Some(controller) => controller,
+            None => self.for_site(site),
+        }
+    }
+
+    pub fn for_client(&self, client: &Client) -> &UnifiController {
+        self.route(client.controller.as_deref(), &client.site)
+    }
+
+    /// Controller where the device is associated on the site right now. When `ip` is given,
+    /// the controller has to report that address for the device too
+    pub async fn locate_station(&self, site: &str, mac: &str, ip: Option<IpAddr>) -> Option<&UnifiController> {
+        let same_ip = |d: &DeviceInfo| match (ip, d.ip.as_deref()) {
+            (Some(ip), Some(device_ip)) => device_ip.parse() == Ok(ip),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        for controller in self.controllers.iter() {
+            let Ok(devices) = controller.get_all_devices(site.to_string(), false).await else {
+                continue;
+            };
+
+            if devices.iter().any(|d| d.mac.eq_ignore_ascii_case(mac) && same_ip(d)) {
+                return Some(controller);
+            }
+        }
+
+        None
+    }
+
+    pub fn all(&self) -> &[UnifiController] {
+        &self.controllers
+    }
+
+    /// Sites of every controller, each one tagged with the controller that owns it
+    /// Sites of the controllers that answered, an error only when none did
+    pub async fn get_sites(&self) -> Result<Vec<Site>, UnifiError> {
+        let mut sites: Vec<Site> = vec![];
+        let mut error = None;
+
+        for controller in self.controllers.iter() {
+            match controller.get_sites().await {
+                Ok(mut s) => sites.append(&mut s),
+                Err(e) => {
+                    println!("{e}");
+                    error = Some(e);
+                }
+            }
+        }
+
+        match error {
+            Some(e) if sites.is_empty() => Err(e),
+            _ => {
+                self.record_sites(&sites);
+                Ok(sites)
+            }
+        }
+    }
+
+    /// Keeps the owners of every site name, warning when a name shows up on a second controller
+    pub fn record_sites(&self, sites: &[Site]) {
+        let mut owners: HashMap<String, Vec<String>> = HashMap::new();
+        for site in sites {
+            owners.entry(site.name.clone()).or_default().push(site.controller.clone());
+        }
+
+        let mut known = self.site_owners.write().unwrap();
+        for (site, controllers) in owners.iter() {
+            let mapped = self.controllers.iter().any(|c| c.serves(site));
+            if controllers.len() > 1 && !mapped && known.get(site) != Some(controllers) {
+                println!(
+                    "Warning: site \"{site}\" exists on the controllers {}, guests are routed by the controller they were found on",
+                    controllers.join(", ")
+                );
+            }
+        }
+
+        known.extend(owners);
+    }
+}
+
 impl UnifiController {
-    pub async fn new(base_url: String, username: String, password: String) -> Self {
-        let mut unifi = Self {
-            base_url,
-            username,
-            password,
-            authentication_time: None,
+    pub async fn new(config: &UnifiConfig) -> Self {
+        let cookies = Arc::new(Jar::default());
+
+        let unifi = Self {
+            name: config.name.clone(),
+            sites: config.sites.clone().unwrap_or_default(),
+            base_url: config.url.clone(),
+            username: config.username.clone(),
+            password: config.password.clone(),
+            session: Arc::new(RwLock::new(UnifiSession {
+                platform: config.platform.unwrap_or_default(),
+                ..Default::default()
+            })),
+            login_lock: Arc::new(Mutex::new(())),
             client: reqwest::Client::builder()
                 .danger_accept_invalid_certs(true) // Ignorar certificados inválidos
-                .cookie_store(true) // Habilitando o armazenamento e envio automatico de cookies
+                .cookie_provider(cookies.clone()) // Habilitando o armazenamento e envio automatico de cookies
                 .build()
                 .unwrap(),
+            cookies,
         };
 
-        let _ = unifi.authentication_api().await;
+        if let Err(e) = unifi.authentication_api().await {
+            println!("{e}");
+        }
+
         unifi
     }
 
-    fn check_authentication(&mut self) -> bool {
-        match self.authentication_time {
-            Some(t) => {
-                let duration = t.elapsed();
-                if duration <= Duration::from_secs(60 * 10) {
-                    return true;
-                }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-                self.authentication_time = None;
-                false
-            }
-            None => false,
+    pub fn serves(&self, site: &str) -> bool {
+        self.sites.iter().any(|s| s == site)
+    }
+
+    // The session lifetime is decided by the controller, an expired one is detected on use
+    fn check_authentication(&self) -> bool {
+        self.session.read().unwrap().authentication_time.is_some()
+    }
+
+    fn platform(&self) -> UnifiPlatform {
+        self.session.read().unwrap().platform
+    }
+
+    fn generation(&self) -> u64 {
+        self.session.read().unwrap().generation
+    }
+
+    fn root_url(&self) -> &str {
+        self.base_url.trim_end_matches('/').trim_end_matches("/api")
+    }
+
+    fn api_url(&self) -> String {
+        match self.platform() {
+            UnifiPlatform::UnifiOs => format!("{}/proxy/network/api", self.root_url()),
+            _ => format!("{}/api", self.root_url()),
         }
     }
 
-    pub async fn authentication_api(&mut self) -> Result<(), reqwest::Error> {
+    fn login_url(&self) -> String {
+        match self.platform() {
+            UnifiPlatform::UnifiOs => format!("{}/api/auth/login", self.root_url()),
+            _ => format!("{}/login", self.api_url()),
+        }
+    }
+
+    fn site_url(&self, site: &str, path: &str) -> String {
+        format!("{}/s/{}/{}", self.api_url(), site, path)
+    }
+
+    fn events_url(&self, site: &str) -> String {
+        let root = self
+            .root_url()
+            .replacen("https://", "wss://", 1)
+            .replacen("http://", "ws://", 1);
+
+        match self.platform() {
+            UnifiPlatform::UnifiOs => format!("{root}/proxy/network/wss/s/{site}/events"),
+            _ => format!("{root}/wss/s/{site}/events"),
+        }
+    }
+
+    // Requests that change state on UniFi OS must carry the CSRF token issued at login
+    fn write_request(&self, method: reqwest::Method, url: String) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, url);
+
+        match &self.session.read().unwrap().csrf_token {
+            Some(token) => request.header("X-CSRF-Token", token),
+            None => request,
+        }
+    }
+
+    fn update_csrf_token(&self, res: &reqwest::Response) {
+        let headers = res.headers();
+        let token = headers
+            .get("x-updated-csrf-token")
+            .or_else(|| headers.get("x-csrf-token"))
+            .and_then(|v| v.to_str().ok());
+
+        if let Some(t) = token {
+            self.session.write().unwrap().csrf_token = Some(t.to_string());
+        }
+    }
+
+    // UniFi OS answers 200 on the root path, the legacy controller redirects to /manage
+    pub async fn detect_platform(&self) -> Result<UnifiPlatform, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+
+        let res = client.get(format!("{}/", self.root_url())).send().await?;
+
+        let platform = match res.status() {
+            reqwest::StatusCode::OK => UnifiPlatform::UnifiOs,
+            _ => UnifiPlatform::Legacy,
+        };
+
+        self.session.write().unwrap().platform = platform;
+        Ok(platform)
+    }
+
+    pub async fn authentication_api(&self) -> Result<(), UnifiError> {
+        self.login(self.generation()).await
+    }
+
+    // Single-flight login: requests that saw the same dropped session wait for one login
+    // and reuse it instead of each one logging in again
+    async fn login(&self, generation: u64) -> Result<(), UnifiError> {
+        let _lock = self.login_lock.lock().await;
+
+        if self.generation() != generation && self.check_authentication() {
+            return Ok(());
+        }
+
+        if self.platform() == UnifiPlatform::Auto {
+            self.detect_platform().await?;
+        }
+
         let body = HashMap::from([
             ("username", self.username.as_str()),
             ("password", self.password.as_str()),
@@ -182,166 +690,190 @@ impl UnifiController {
 
         let res = self
             .client
-            .post(format!("{}/login", self.base_url))
+            .post(self.login_url())
             .json(&body)
             .send()
             .await?;
 
+        self.session.write().unwrap().authentication_time = None;
+
         match res.status() {
             reqwest::StatusCode::OK => {
-                self.authentication_time = Some(Instant::now());
+                self.session.write().unwrap().csrf_token = None;
+                self.update_csrf_token(&res);
+
+                let mut session = self.session.write().unwrap();
+                session.authentication_time = Some(Instant::now());
+                session.generation += 1;
                 Ok(())
             }
 
-            _ => Ok(()),
+            status => Err(UnifiError::Auth(format!("login rejected ({status})"))),
         }
     }
 
-    pub async fn authorize_device(
-        &mut self,
-        site: &String,
-        mac: &String,
-        minutes: &u16,
-    ) -> Result<(), reqwest::Error> {
+    // Sends a request to the controller and returns the `data` field of the answer.
+    // When the session was dropped by the controller, logs in again and replays it once.
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        url: String,
+        body: Option<Value>,
+    ) -> Result<Value, UnifiError> {
+        let generation = self.ensure_authentication().await?;
+
+        match self.send_once(method.clone(), url.clone(), body.clone()).await {
+            Err(UnifiError::Auth(_)) => {
+                self.login(generation).await?;
+                self.send_once(method, url, body).await
+            }
+
+            res => res,
+        }
+    }
+
+    // Logs in when there is no session yet and returns the generation of the one in use
+    async fn ensure_authentication(&self) -> Result<u64, UnifiError> {
+        let generation = self.generation();
         if !self.check_authentication() {
-            self.authentication_api().await?;
+            self.login(generation).await?;
         }
 
-        let body = DeviceAuthorization::new(mac.to_string(), *minutes);
+        Ok(self.generation())
+    }
 
-        let _res = self
-            .client
-            .post(format!("{}/s/{}/cmd/stamgr", self.base_url, site))
-            .json(&body)
-            .send()
-            .await?;
+    async fn send_once(
+        &self,
+        method: reqwest::Method,
+        url: String,
+        body: Option<Value>,
+    ) -> Result<Value, UnifiError> {
+        let mut request = match method {
+            reqwest::Method::GET => self.client.get(url),
+            _ => self.write_request(method, url),
+        };
 
-        Ok(())
-    }
+        if let Some(b) = body {
+            request = request.json(&b);
+        }
 
-    pub async fn unauthorize_device(
-        &mut self,
-        site: &String,
-        mac: &String,
-    ) -> Result<(), reqwest::Error> {
-        if !self.check_authentication() {
-            self.authentication_api().await?;
+        let res = request.send().await?;
+        self.update_csrf_token(&res);
+
+        let status = res.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            return Err(UnifiError::Auth(format!("session rejected ({status})")));
         }
 
-        let body = DeviceUnauthorize::new(mac.to_string());
+        let text = res.text().await?;
+        let res = serde_json::from_str::<ApiResponse>(&text).map_err(|e| {
+            if status.is_success() {
+                UnifiError::from(e)
+            } else {
+                UnifiError::Decode(format!("unexpected answer ({status})"))
+            }
+        })?;
 
-        let _res = self
-            .client
-            .post(format!("{}/s/{}/cmd/stamgr", self.base_url, site))
-            .json(&body)
-            .send()
-            .await?;
+        res.into_result()
+    }
+
+    async fn send_command(&self, site: &str, command: impl Serialize) -> Result<Value, UnifiError> {
+        let body = serde_json::to_value(command)?;
+        self.send(reqwest::Method::POST, self.site_url(site, "cmd/stamgr"), Some(body))
+            .await
+    }
+
+    pub async fn authorize_device(
+        &self,
+        site: &str,
+        mac: &str,
+        minutes: u16,
+        limits: &ClientLimits,
+    ) -> Result<(), UnifiError> {
+        let body = DeviceAuthorization::new(mac.to_string(), minutes, limits);
+        self.send_command(site, body).await?;
+
+        Ok(())
+    }
+
+    pub async fn unauthorize_device(&self, site: &str, mac: &str) -> Result<(), UnifiError> {
+        let body = DeviceUnauthorize::new(mac.to_string());
+        self.send_command(site, body).await?;
 
         Ok(())
     }
 
     pub async fn rename_device(
-        &mut self,
+        &self,
         id: String,
         site: String,
         name: String,
-    ) -> Result<(), reqwest::Error> {
-        if !self.check_authentication() {
-            self.authentication_api().await?;
-        }
-
-        let body = HashMap::from([("name", name.as_str())]);
-        let _res = self
-            .client
-            .put(format!("{}/s/{}/upd/user/{}", self.base_url, site, id))
-            .json(&body)
-            .send()
-            .await?;
+    ) -> Result<(), UnifiError> {
+        let body = serde_json::json!({ "name": name });
+        self.send(
+            reqwest::Method::PUT,
+            self.site_url(&site, &format!("upd/user/{id}")),
+            Some(body),
+        )
+        .await?;
 
         Ok(())
     }
 
-    pub async fn get_guest_devices(&mut self, site: String) -> Result<Vec<DeviceInfo>, reqwest::Error> {
-        if !self.check_authentication() {
-            self.authentication_api().await?;
-        }
-
-        let res = self
-            .client
-            .get(format!("{}/s/{}/stat/guest", self.base_url, site))
-            .send()
+    pub async fn get_guest_devices(&self, site: String) -> Result<Vec<DeviceInfo>, UnifiError> {
+        let data = self
+            .send(reqwest::Method::GET, self.site_url(&site, "stat/guest"), None)
             .await?;
 
-        let res = res.json::<ApiResponse>().await?;
-        let mut list: Vec<DeviceInfo> = vec![];
-        
+        Ok(serde_json::from_value(data)?)
+    }
 
-        if let Value::Array(array) = res.data {
-            let clients: Result<Vec<DeviceInfo>, _> = serde_json::from_value(Value::Array(array));
-            if let Ok(cs) = clients { list = cs; }
-        }
+    /// Every network device of the site (access points, switches and gateways)
+    pub async fn get_network_devices(&self, site: &str) -> Result<Vec<AccessPoint>, UnifiError> {
+        let data = self
+            .send(reqwest::Method::GET, self.site_url(site, "stat/device"), None)
+            .await?;
 
-        Ok(list)
+        Ok(serde_json::from_value(data)?)
     }
 
-    pub async fn get_all_devices(&mut self, site: String, is_guest: bool) -> Result<Vec<DeviceInfo>, reqwest::Error> {
-        if !self.check_authentication() {
-            self.authentication_api().await?;
-        }
+    pub async fn get_access_points(&self, site: &str) -> Result<Vec<AccessPoint>, UnifiError> {
+        let mut devices = self.get_network_devices(site).await?;
+        devices.retain(|d| d.kind.as_deref() == Some("uap"));
 
-        let res = self
-            .client
-            .get(format!("{}/s/{}/stat/sta", self.base_url, site))
-            .send()
-            .await?;
-
-        let res = res.json::<ApiResponse>().await?;
-        let mut list: Vec<DeviceInfo> = vec![];
+        Ok(devices)
+    }
 
-        if let Value::Array(array) = res.data {
-            let clients: Result<Vec<DeviceInfo>, _> = serde_json::from_value(Value::Array(array));
+    pub async fn get_all_devices(&self, site: String, is_guest: bool) -> Result<Vec<DeviceInfo>, UnifiError> {
+        let data = self
+            .send(reqwest::Method::GET, self.site_url(&site, "stat/sta"), None)
+            .await?;
 
-            if let Ok(cs) = clients {
-                let mut cs = cs;
-                if is_guest {
-                    cs.retain( |d| d.is_guest.unwrap_or(false) );
-                }
-                list = cs
-            }
+        let mut list: Vec<DeviceInfo> = serde_json::from_value(data)?;
+        if is_guest {
+            list.retain( |d| d.is_guest.unwrap_or(false) );
         }
 
         Ok(list)
     }
     
-    pub async fn disconnect_client(&mut self, client: &Client) {
-        if !self.check_authentication() {
-           let _ = self.authentication_api().await;
-        }
-
+    pub async fn disconnect_client(&self, client: &Client) -> Result<(), UnifiError> {
         let disconnect_device = DisconnectDevice::new(client.mac.clone());
+        self.send_command(&client.site, disconnect_device).await?;
 
-        let _ = self
-            .client
-            .post( format!("{}/s/{}/cmd/stamgr", self.base_url, client.site.clone()) )
-            .json(&disconnect_device)
-            .send()
-            .await;
+        Ok(())
     }
 
+    pub async fn conect_client(&self, client: &Client) -> Result<(), UnifiError> {
+        let minutes = client
+            .time_connection
+            .parse::<u16>()
+            .map_err(|e| UnifiError::Decode(format!("invalid connection time: {e}")))?;
 
-    pub async fn conect_client(&mut self, client: &Client) {
-        if !self.check_authentication() {
-            let _ = self.authentication_api().await;
-        }
-
-        let _ = self
-            .authorize_device(
-                &client.site,
-                &client.mac,
-                &client.time_connection.parse::<u16>().unwrap(),
-            )
-            .await;
+        let limits = client.limits.clone().unwrap_or_default();
+        self.authorize_device(&client.site, &client.mac, minutes, &limits).await?;
 
+        // Renaming and the user group are applied on a best-effort basis, the guest is already authorized
         let devices = self
             .get_all_devices(client.site.clone(), true)
             .await
@@ -349,30 +881,200 @@ impl UnifiController {
 
         let device = devices.iter().find(|c| c.mac == client.mac.clone());
 
-        if let Some(d) = device {
-            let name = format!("({})", client.full_name.clone());
-            let _ = self
-                .rename_device(d.record_id.clone().unwrap(), client.site.clone(), name)
-                .await;
+        let Some(record_id) = device.and_then(|d| d.record_id.clone()) else {
+            return Ok(());
+        };
+
+        let name = format!("({})", client.full_name.clone());
+        if let Err(e) = self.rename_device(record_id.clone(), client.site.clone(), name).await {
+            println!("{e}");
+        }
+
+        // Only logged when it fails, like the rename, the guest stays in the group it already had
+        if let Some(group) = &client.user_group
+            && let Err(e) = self.assign_user_group(&client.site, &record_id, group).await
+        {
+            println!("{e}");
         }
+
+        Ok(())
     }
 
-    pub async fn reject_client(&mut self, client: &Client) {
-        if !self.check_authentication() {
-            let _ = self.authentication_api().await;
+    pub async fn reject_client(&self, client: &Client) -> Result<(), UnifiError> {
+        self.disconnect_client(client).await
+    }
+
+    pub async fn block_device(&self, site: &str, mac: &str) -> Result<(), UnifiError> {
+        self.send_command(site, BlockDevice::new_block(mac.to_string())).await?;
+        Ok(())
+    }
+
+    pub async fn unblock_device(&self, site: &str, mac: &str) -> Result<(), UnifiError> {
+        self.send_command(site, BlockDevice::new_unblock(mac.to_string())).await?;
+        Ok(())
+    }
+
+    /// Opens the controller events websocket of the site, logging in again once if the session was dropped
+    pub async fn events(&self, site: &str) -> Result<UnifiEventStream, UnifiError> {
+        let generation = self.ensure_authentication().await?;
+
+        match self.connect_events(site).await {
+            Err(UnifiError::Auth(_)) => {
+                self.login(generation).await?;
+                self.connect_events(site).await
+            }
+
+            res => res,
+        }
+    }
+
+    async fn connect_events(&self, site: &str) -> Result<UnifiEventStream, UnifiError> {
+        let root = reqwest::Url::parse(self.root_url()).map_err(|e| UnifiError::Decode(e.to_string()))?;
+
+        let mut request = self
+            .events_url(site)
+            .into_client_request()
+            .map_err(|e| UnifiError::Socket(e.to_string()))?;
+
+        if let Some(cookie) = self.cookies.cookies(&root) {
+            request.headers_mut().insert("Cookie", cookie);
         }
 
-        self.disconnect_client(client).await;
+        UnifiEventStream::connect(request).await
+    }
+
+    pub async fn get_sites(&self) -> Result<Vec<Site>, UnifiError> {
+        let data = self
+            .send(reqwest::Method::GET, format!("{}/self/sites", self.api_url()), None)
+            .await?;
+
+        let mut sites: Vec<Site> = serde_json::from_value(data)?;
+        sites.iter_mut().for_each(|s| s.controller = self.name.clone());
+
+        Ok(sites)
+    }
+
+    pub async fn create_vouchers(&self, site: &str, voucher: VoucherCreation) -> Result<Vec<Voucher>, UnifiError> {
+        let body = serde_json::to_value(voucher)?;
+        let data = self
+            .send(reqwest::Method::POST, self.site_url(site, "cmd/hotspot"), Some(body))
+            .await?;
+
+        // The controller only answers with the batch creation time
+        let create_time = data
+            .get(0)
+            .and_then(|d| d.get("create_time"))
+            .cloned()
+            .ok_or(UnifiError::Decode(String::from("missing voucher create_time")))?;
+
+        let data = self
+            .send(
+                reqwest::Method::POST,
+                self.site_url(site, "stat/voucher"),
+                Some(serde_json::json!({ "create_time": create_time })),
+            )
+            .await?;
+
+        Ok(serde_json::from_value(data)?)
+    }
+
+    pub async fn get_user_groups(&self, site: &str) -> Result<Vec<UserGroup>, UnifiError> {
+        let data = self
+            .send(reqwest::Method::GET, self.site_url(site, "rest/usergroup"), None)
+            .await?;
+
+        Ok(serde_json::from_value(data)?)
+    }
+
+    /// Moves the device record to the user group with this name
+    pub async fn assign_user_group(&self, site: &str, record_id: &str, group: &str) -> Result<(), UnifiError> {
+        let group = self
+            .get_user_groups(site)
+            .await?
+            .into_iter()
+            .find(|g| g.name == group)
+            .ok_or(UnifiError::Api(format!("user group {group} not found on site {site}")))?;
+
+        let body = serde_json::json!({ "usergroup_id": group.id });
+        self.send(
+            reqwest::Method::PUT,
+            self.site_url(site, &format!("upd/user/{record_id}")),
+            Some(body),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_wlans(&self, site: &str) -> Result<Vec<Wlan>, UnifiError> {
+        let data = self
+            .send(reqwest::Method::GET, self.site_url(site, "rest/wlanconf"), None)
+            .await?;
+
+        Ok(serde_json::from_value(data)?)
+    }
+
+    pub async fn set_wlan_passphrase(&self, site: &str, id: &str, passphrase: &str) -> Result<(), UnifiError> {
+        let body = serde_json::json!({ "x_passphrase": passphrase });
+        self.send(
+            reqwest::Method::PUT,
+            self.site_url(site, &format!("rest/wlanconf/{id}")),
+            Some(body),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_guest_access(&self, site: &str) -> Result<GuestAccessSetting, UnifiError> {
+        let data = self
+            .send(reqwest::Method::GET, self.site_url(site, "rest/setting/guest_access"), None)
+            .await?;
+
+        let mut settings: Vec<GuestAccessSetting> = serde_json::from_value(data)?;
+        settings
+            .pop()
+            .ok_or(UnifiError::Decode(String::from("missing guest_access setting")))
+    }
+
+    pub async fn set_guest_access(&self, site: &str, setting: &GuestAccessSetting) -> Result<GuestAccessSetting, UnifiError> {
+        let body = serde_json::to_value(setting)?;
+        let data = self
+            .send(
+                reqwest::Method::PUT,
+                self.site_url(site, &format!("rest/setting/guest_access/{}", setting.id)),
+                Some(body),
+            )
+            .await?;
+
+        let mut settings: Vec<GuestAccessSetting> = serde_json::from_value(data)?;
+        Ok(settings.pop().unwrap_or(setting.clone()))
+    }
+
+    pub async fn get_vouchers(&self, site: &str) -> Result<Vec<Voucher>, UnifiError> {
+        let data = self
+            .send(reqwest::Method::GET, self.site_url(site, "stat/voucher"), None)
+            .await?;
+
+        Ok(serde_json::from_value(data)?)
+    }
+
+    pub async fn revoke_voucher(&self, site: &str, id: String) -> Result<(), UnifiError> {
+        let body = serde_json::to_value(VoucherRevoke::new(id))?;
+        self.send(reqwest::Method::POST, self.site_url(site, "cmd/hotspot"), Some(body))
+            .await?;
+
+        Ok(())
     }
 }
 
 // Guards
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for UnifiController {
+impl<'r> FromRequest<'r> for UnifiControllers {
     type Error = ();
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let unifi = request.guard::<&UnifiState>().await.unwrap().lock().await;
-        Outcome::Success(unifi.clone())
+        let unifi = request.guard::<&UnifiState>().await.unwrap();
+        Outcome::Success(unifi.inner().clone())
     }
 }
@@ -0,0 +1,48 @@
+use crate::model::entity::client::{Client, ClientStatus};
+use rocket::serde::Serialize;
+use rocket::tokio::sync::broadcast::{self, Receiver, Sender};
+
+const CAPACITY: usize = 256;
+
+// Structs
+/// Status changes of the client records, pushed to the guests waiting on the portal
+#[derive(Clone)]
+pub struct ClientEvents {
+    sender: Sender<ClientStatusEvent>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ClientStatusEvent {
+    pub client_id: String,
+    pub mac: String,
+    pub site: String,
+    pub status: ClientStatus,
+}
+
+// Impls
+impl ClientEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    /// Nobody listening is not an error, the event is just dropped
+    pub fn publish(&self, client: &Client) {
+        let _ = self.sender.send(ClientStatusEvent {
+            client_id: client.id.clone(),
+            mac: client.mac.to_lowercase(),
+            site: client.site.clone(),
+            status: client.status.clone(),
+        });
+    }
+
+    pub fn subscribe(&self) -> Receiver<ClientStatusEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for ClientEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use chrono::Local;
 use rocket::{
     response::status::Custom,
@@ -9,6 +11,7 @@ pub type CustomError = Custom<Json<Error>>;
 pub type Unauthorized = Custom<Json<Error>>;
 pub type BadRequest = Custom<Json<Error>>;
 pub type NotFound = Custom<Json<Error>>;
+pub type BadGateway = Custom<Json<Error>>;
 
 // Structs
 #[derive(Serialize, Deserialize)]
@@ -16,6 +19,9 @@ pub struct Error {
     pub err: String,
     pub time: String,
     pub status: u16,
+    /// Message per form field, on invalid portal forms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<HashMap<String, String>>,
 }
 
 // Impls
@@ -25,6 +31,7 @@ impl Error {
             err: msg.to_string(),
             time,
             status,
+            fields: None,
         };
 
         Custom(rocket::http::Status { code: status }, Json(error))
@@ -41,4 +48,26 @@ impl Error {
     pub fn new_not_found(msg: &str) -> NotFound {
         Self::new_with_custom(msg, Local::now().to_string(), 404)
     }
+
+    pub fn new_bad_gateway(msg: &str) -> BadGateway {
+        Self::new_with_custom(msg, Local::now().to_string(), 502)
+    }
+
+    pub fn new_invalid_fields(fields: HashMap<String, String>) -> BadRequest {
+        let mut error = Self::new_bad_request("Invalid Form Field(s)");
+        error.1.fields = Some(fields);
+        error
+    }
+
+    pub fn new_too_many_requests(msg: &str) -> CustomError {
+        Self::new_with_custom(msg, Local::now().to_string(), 429)
+    }
+
+    pub fn new_service_unavailable(msg: &str) -> CustomError {
+        Self::new_with_custom(msg, Local::now().to_string(), 503)
+    }
+
+    pub fn new_internal_server_error(msg: &str) -> CustomError {
+        Self::new_with_custom(msg, Local::now().to_string(), 500)
+    }
 }
@@ -0,0 +1,100 @@
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+};
+
+use crate::configurations::config::{EmailVerificationConfig, SmtpConfig, SmtpSecurity};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+
+// Types
+/// Missing when the email verification is off or its SMTP settings are invalid
+pub type Mailers = Option<Arc<dyn Mailer>>;
+
+// Structs
+#[derive(Debug)]
+pub struct MailError(pub String);
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+// Traits
+#[rocket::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), MailError>;
+}
+
+// Impls
+impl Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Email could not be sent: {}", self.0)
+    }
+}
+
+impl std::error::Error for MailError {}
+
+impl SmtpMailer {
+    pub fn new(config: &SmtpConfig) -> Result<Self, MailError> {
+        let builder = match config.security {
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|e| MailError(e.to_string()))?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| MailError(e.to_string()))?,
+        };
+
+        let mut builder = builder.port(config.port);
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| MailError(e.to_string()))?;
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), MailError> {
+        let to = to.parse::<Mailbox>().map_err(|e| MailError(e.to_string()))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body.to_string())
+            .map_err(|e| MailError(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| MailError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+// Functions
+/// Relay of the email verification, built once and shared by every request
+pub fn mailer_for(config: Option<&EmailVerificationConfig>) -> Mailers {
+    match SmtpMailer::new(&config?.smtp) {
+        Ok(mailer) => Some(Arc::new(mailer)),
+        Err(e) => {
+            println!("{e}");
+            None
+        }
+    }
+}
@@ -2,4 +2,9 @@ pub mod error;
 pub mod monitoring;
 pub mod responses;
 pub mod validator;
-pub mod generator;
\ No newline at end of file
+pub mod generator;
+pub mod wlan;
+pub mod client_events;
+pub mod terms;
+pub mod mailer;
+pub mod sms;
\ No newline at end of file
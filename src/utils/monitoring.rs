@@ -1,21 +1,38 @@
 use crate::{
-    configurations::config::{ApproversConfig, ClientsConfig, LdapConfig, UsersConfig}, ldap::ldap::LdapConnection, model::{
-        entity::{admin::Admin, approver::Approver, client::{Client, ClientStatus}, user::User},
+    configurations::config::{ApproversConfig, ClientsConfig, LdapConfig, UsersConfig, WlansConfig}, ldap::ldap::LdapConnection, model::{
+        entity::{admin::Admin, approver::Approver, blocked_device::BlockedDevice, client::{Client, ClientStatus}, psk_rotation::PskRotation, reconciliation::Reconciliation, session::Session, user::User, verification::Verification},
         repository::{mongo_repository::MongoRepository, Repository},
-    }, unifi::unifi::{DeviceInfo, UnifiController}
+    }, unifi::{events::UnifiEvent, unifi::{DeviceInfo, Site, UnifiController, UnifiControllers}}
 };
 use bcrypt::{hash, DEFAULT_COST};
-use chrono::Local;
+use chrono::{Local, TimeZone};
 use ldap3::Ldap;
 use rocket_db_pools::mongodb::Database;
 use bson::{doc, oid::ObjectId, DateTime};
-use super::generator;
+use super::{client_events::ClientEvents, generator, wlan};
+use crate::security::verification::verifying_devices;
 
 // Struct
 pub struct ClientsMonitoring {
     config: ClientsConfig,
     repo: MongoRepository<Client>,
-    unifi: UnifiController,
+    blocklist_repo: MongoRepository<BlockedDevice>,
+    session_repo: MongoRepository<Session>,
+    reconciliation_repo: MongoRepository<Reconciliation>,
+    verification_repo: MongoRepository<Verification>,
+    unifi: UnifiControllers,
+    events: ClientEvents,
+}
+
+pub struct EventsMonitoring {
+    repo: MongoRepository<Client>,
+    events: ClientEvents,
+}
+
+pub struct WlanMonitoring {
+    config: WlansConfig,
+    repo: MongoRepository<PskRotation>,
+    unifi: UnifiControllers,
 }
 
 pub struct LdapMonitoring {
@@ -28,43 +45,195 @@ pub struct LdapMonitoring {
 // Impls
 #[allow(unused)]
 impl ClientsMonitoring {
-    pub fn new(database: Database, unifi: UnifiController, config: ClientsConfig) -> Self {
+    pub fn new(database: Database, unifi: UnifiControllers, config: ClientsConfig, events: ClientEvents) -> Self {
         Self {
             config,
-            repo: MongoRepository::new(database),
+            repo: MongoRepository::new(database.clone()),
+            blocklist_repo: MongoRepository::new(database.clone()),
+            session_repo: MongoRepository::new(database.clone()),
+            reconciliation_repo: MongoRepository::new(database.clone()),
+            verification_repo: MongoRepository::new(database),
             unifi,
+            events,
         }
     }
 
     pub async fn all(&mut self) {
-        let mut sites: Vec<String> = vec![];
-        let mut clients = self.repo.find_all().await;
-
-        for c in clients.iter() {
-            if !sites.contains(&c.site) {
-                sites.push(c.site.clone());
+        let mut sites: Vec<Site> = vec![];
+        for controller in self.unifi.all() {
+            match controller.get_sites().await {
+                Ok(mut s) => sites.append(&mut s),
+                Err(e) => println!("{e}"),
             }
         }
 
+        self.unifi.record_sites(&sites);
+        let clients = self.repo.find_all().await;
+
+        // The same site name can exist on several controllers, each guest is checked on its own
         for site in sites.iter() {
-            let res = self.unifi.get_guest_devices(site.clone()).await;
-            if res.is_err() {
-                break;
-            }
-            let devices = res.unwrap();
+            let Some(unifi) = self.unifi.get(&site.controller).cloned() else {
+                continue;
+            };
+
+            let devices = match unifi.get_guest_devices(site.name.clone()).await {
+                Ok(d) => d,
+                Err(e) => {
+                    println!("{e}");
+                    continue;
+                }
+            };
+
+            let mut site_clients: Vec<Client> = clients
+                .iter()
+                .filter(|c| c.site == site.name && self.unifi.for_client(c).name() == site.controller)
+                .cloned()
+                .collect();
 
             if let Some(max_time) = &self.config.expiration_time {
-                self.delete_client_registration_expired(&mut clients, *max_time).await;
+                self.delete_client_registration_expired(&mut site_clients, *max_time).await;
             }
         
-            self.check_and_update_client_fields(&mut clients, &devices);
+            self.reconcile(&unifi, &site.name, &site_clients, &devices).await;
+
+            let snapshot = site_clients.clone();
+            self.check_and_update_client_fields(&mut site_clients, &devices);
 
-            for i in 0..clients.len() {
-                let r = self.repo.update(clients.remove(0)).await;
+            match unifi.get_all_devices(site.name.clone(), true).await {
+                Ok(connected) => self.record_sessions(unifi.name(), &site.name, &site_clients, &connected).await,
+                Err(e) => println!("{e}"),
+            }
+
+            for (before, after) in snapshot.iter().zip(site_clients.iter()) {
+                self.save_monitored_fields(before, after).await;
             }
         }
     }
     
+    // Compares the guests authorized on the site with our records and keeps the latest report,
+    // unauthorizing the unknown ones when the policy is on
+    pub async fn reconcile(&self, unifi: &UnifiController, site: &str, clients: &[Client], devices: &[DeviceInfo]) -> Reconciliation {
+        let mut report = Reconciliation::new_with_devices(site.to_string(), clients, devices);
+        report.controller = Some(unifi.name().to_string());
+
+        // Guests reading their verification code are authorized without a record on purpose
+        let verifying = verifying_devices(&self.verification_repo, site).await;
+        report.unknown_guests.retain(|g| !verifying.contains(&g.mac));
+
+        // The clients were read at the start of the cycle, a guest approved since then is not unknown
+        if !report.unknown_guests.is_empty() {
+            let macs: Vec<&str> = report.unknown_guests.iter().map(|g| g.mac.as_str()).collect();
+            let approved = self
+                .repo
+                .find(doc! { "site": site, "mac": { "$in": macs }, "status": "Approved" })
+                .await;
+
+            report.unknown_guests.retain(|g| !approved.iter().any(|c| c.mac == g.mac));
+        }
+
+        if self.config.unauthorize_unknown.unwrap_or(false) {
+            for guest in report.unknown_guests.iter_mut() {
+                match unifi.unauthorize_device(site, &guest.mac).await {
+                    Ok(_) => guest.unauthorized = true,
+                    Err(e) => println!("{e}"),
+                }
+            }
+        }
+
+        let query = doc! { "site": site, "controller": { "$in": [unifi.name(), null] } };
+        match self.reconciliation_repo.find_one(query).await {
+            Some(previous) => {
+                report.id = previous.id;
+                self.reconciliation_repo.update(report.clone()).await;
+            }
+
+            None => {
+                self.reconciliation_repo.save(report.clone()).await;
+            }
+        }
+
+        report
+    }
+
+    // Keeps one session per association of the approved guests connected right now,
+    // closing the ones whose device left or associated again
+    pub async fn record_sessions(&self, controller: &str, site: &str, clients: &[Client], devices: &[DeviceInfo]) {
+        let query = doc! { "site": site, "controller": { "$in": [controller, null] }, "end": null };
+        let mut open = self.session_repo.find(query).await;
+
+        for device in devices {
+            let client = clients
+                .iter()
+                .filter(|c| c.mac == device.mac && c.status == ClientStatus::Approved)
+                .max_by_key(|c| c.start_time);
+
+            let Some(client) = client else { continue };
+
+            match open.iter().position(|s| s.mac == device.mac) {
+                Some(pos) if open[pos].is_same_association(device) => {
+                    let mut session = open.remove(pos);
+                    session.update_with_device(device);
+                    self.session_repo.update(session).await;
+                }
+
+                _ => {
+                    self.session_repo.save(Session::new_with_device(client, device)).await;
+                }
+            }
+        }
+
+        for mut session in open {
+            session.close();
+            self.session_repo.update(session).await;
+        }
+    }
+
+    // Writes only the fields read from the controller, so a change made through the API during
+    // the cycle is kept. The expiry is skipped when the session was changed in the meantime
+    async fn save_monitored_fields(&self, before: &Client, after: &Client) {
+        let Ok(id) = ObjectId::parse_str(&after.id) else {
+            return;
+        };
+
+        let fields = doc! {
+            "hostname": bson::to_bson(&after.hostname).unwrap(),
+            "ap": bson::to_bson(&after.ap).unwrap(),
+            "last_seen": bson::to_bson(&after.last_seen).unwrap(),
+            "tx_bytes": bson::to_bson(&after.tx_bytes).unwrap(),
+            "rx_bytes": bson::to_bson(&after.rx_bytes).unwrap(),
+        };
+        self.repo.update_all(doc! { "_id": id }, doc! { "$set": fields }).await;
+
+        if after.status != before.status {
+            let query = doc! {
+                "_id": id,
+                "status": bson::to_bson(&before.status).unwrap(),
+                "time_connection": &before.time_connection,
+            };
+
+            let status = doc! { "$set": { "status": bson::to_bson(&after.status).unwrap() } };
+            self.repo.update_all(query, status).await;
+        }
+    }
+
+    // Unblocks on the controller the devices whose block expired
+    pub async fn release_expired_blocks(&mut self) {
+        let entries = self.blocklist_repo.find_all().await;
+
+        for entry in entries.into_iter().filter(|b| b.is_expired()) {
+            let res = self
+                .unifi
+                .route(entry.controller.as_deref(), &entry.site)
+                .unblock_device(&entry.site, &entry.mac)
+                .await;
+
+            match res {
+                Ok(_) => { self.blocklist_repo.delete(entry).await; }
+                Err(e) => println!("{e}"),
+            }
+        }
+    }
+
     pub async fn delete_client_registration_expired(
         &mut self,
         clients: &mut Vec<Client>,
@@ -105,6 +274,7 @@ impl ClientsMonitoring {
             if let Some(device) = d {
                 if device.expired.unwrap_or(true) {
                     c.status = ClientStatus::Expired;
+                    self.events.publish(c);
                     self.repo.update_all(
                         doc!{
                             "_id": ObjectId::parse_str(&c.id).unwrap()
@@ -119,6 +289,15 @@ impl ClientsMonitoring {
                     c.hostname = device.hostname.clone();
                 }
 
+                // Guests that did not come through the portal redirect have no AP cookie
+                if c.ap.is_none() {
+                    c.ap = device.ap_mac.clone();
+                }
+
+                if let Some(seen) = device.last_seen.and_then(|t| Local.timestamp_opt(t as i64, 0).single()) {
+                    c.last_seen = Some(seen);
+                }
+
                 if device.rx_bytes.is_some() {
                     c.rx_bytes = device.rx_bytes;
                 }
@@ -132,6 +311,97 @@ impl ClientsMonitoring {
 
 }
 
+impl EventsMonitoring {
+    pub fn new(database: Database, events: ClientEvents) -> Self {
+        Self {
+            repo: MongoRepository::new(database),
+            events,
+        }
+    }
+
+    // Applies a controller event to the most recent record of the device
+    pub async fn handle(&self, controller: &str, site: &str, event: &UnifiEvent) {
+        let query = doc! { "mac": event.mac(), "site": site, "controller": { "$in": [controller, null] } };
+        let clients = self.repo.find(query).await;
+        let Some(mut client) = clients.into_iter().max_by_key(|c| c.start_time) else {
+            return;
+        };
+
+        let status = client.status.clone();
+
+        match event {
+            UnifiEvent::Connected(_) => {
+                client.connected = Some(true);
+                client.last_seen = Some(Local::now());
+            }
+
+            UnifiEvent::Disconnected(_) => {
+                client.connected = Some(false);
+                client.last_seen = Some(Local::now());
+            }
+
+            UnifiEvent::Authorized(_) => {
+                if client.status == ClientStatus::Expired {
+                    client.status = ClientStatus::Approved;
+                }
+            }
+
+            UnifiEvent::Expired(_) => {
+                if client.status == ClientStatus::Approved {
+                    client.status = ClientStatus::Expired;
+                }
+            }
+        }
+
+        let changed = client.status != status;
+        if let Some(client) = self.repo.update(client).await
+            && changed
+        {
+            self.events.publish(&client);
+        }
+    }
+}
+
+impl WlanMonitoring {
+    pub fn new(database: Database, unifi: UnifiControllers, config: WlansConfig) -> Self {
+        Self {
+            config,
+            repo: MongoRepository::new(database),
+            unifi,
+        }
+    }
+
+    // Rotates the passphrases older than the configured interval. On the first run the
+    // passphrase already set is recorded instead, so guests are not cut off at startup
+    pub async fn rotate_due(&self) {
+        let interval = chrono::Duration::hours(self.config.rotation_hours as i64);
+
+        for target in self.config.rotate.iter() {
+            let unifi = self.unifi.for_site(&target.site);
+
+            let due = match wlan::latest_rotation(&self.repo, target).await {
+                Some(last) => Local::now() - last.rotated_at >= interval,
+                None => match wlan::import_passphrase(unifi, &self.repo, target).await {
+                    Ok(imported) => imported.is_none(),
+                    Err(e) => {
+                        println!("{e}");
+                        continue;
+                    }
+                },
+            };
+
+            if !due {
+                continue;
+            }
+
+            let res = wlan::rotate_passphrase(unifi, &self.repo, &self.config, target, String::from("schedule")).await;
+            if let Err(e) = res {
+                println!("{e}");
+            }
+        }
+    }
+}
+
 #[allow(unused)]
 impl LdapMonitoring {
     pub fn new(database: Database, config: LdapConfig) -> Self {
@@ -0,0 +1,115 @@
+use std::{
+    fmt::{self, Display},
+    sync::Arc,
+};
+#[cfg(test)]
+use std::sync::Mutex;
+
+use crate::configurations::config::{SmsGatewayConfig, SmsVerificationConfig, SmsWebhookConfig};
+use serde_json::Value;
+
+// Types
+pub type SmsGateways = Arc<dyn SmsGateway>;
+
+// Structs
+#[derive(Debug)]
+pub struct SmsError(pub String);
+
+pub struct WebhookGateway {
+    client: reqwest::Client,
+    config: SmsWebhookConfig,
+}
+
+/// Logs the messages instead of sending them, the tests also keep them to read the codes
+#[derive(Clone, Default)]
+pub struct LogGateway {
+    #[cfg(test)]
+    outbox: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+// Traits
+#[rocket::async_trait]
+pub trait SmsGateway: Send + Sync {
+    async fn send(&self, phone: &str, message: &str) -> Result<(), SmsError>;
+}
+
+// Impls
+impl Display for SmsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SMS could not be sent: {}", self.0)
+    }
+}
+
+impl std::error::Error for SmsError {}
+
+impl WebhookGateway {
+    pub fn new(config: SmsWebhookConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl SmsGateway for WebhookGateway {
+    async fn send(&self, phone: &str, message: &str) -> Result<(), SmsError> {
+        let mut body = self.config.extra.clone().unwrap_or_default();
+        body.insert(self.config.to_field.clone(), Value::from(phone));
+        body.insert(self.config.message_field.clone(), Value::from(message));
+
+        let mut request = self.client.post(&self.config.url).json(&body);
+        for (name, value) in self.config.headers.iter().flatten() {
+            request = request.header(name, value);
+        }
+
+        let res = request.send().await.map_err(|e| SmsError(e.to_string()))?;
+        if !res.status().is_success() {
+            return Err(SmsError(format!("gateway answered {}", res.status())));
+        }
+
+        Ok(())
+    }
+}
+
+impl LogGateway {
+    #[cfg(test)]
+    pub fn sent(&self) -> Vec<(String, String)> {
+        self.outbox.lock().unwrap().clone()
+    }
+}
+
+#[rocket::async_trait]
+impl SmsGateway for LogGateway {
+    async fn send(&self, phone: &str, message: &str) -> Result<(), SmsError> {
+        println!("SMS to {phone}: {message}");
+
+        #[cfg(test)]
+        self.outbox
+            .lock()
+            .unwrap()
+            .push((phone.to_string(), message.to_string()));
+
+        Ok(())
+    }
+}
+
+// Functions
+/// Gateway of the configuration, messages are only logged while none is set
+pub fn gateway_for(config: Option<&SmsVerificationConfig>) -> SmsGateways {
+    match config.map(|c| &c.gateway) {
+        Some(SmsGatewayConfig::Webhook(webhook)) => Arc::new(WebhookGateway::new(webhook.clone())),
+        Some(SmsGatewayConfig::Log) | None => Arc::new(LogGateway::default()),
+    }
+}
+
+/// Digits of the number, keeping the leading `+` of the international format
+pub fn normalize_phone(phone: &str) -> String {
+    let phone = phone.trim();
+    let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+
+    match phone.starts_with('+') {
+        true => format!("+{digits}"),
+        false => digits,
+    }
+}
@@ -0,0 +1,33 @@
+use std::net::IpAddr;
+
+use crate::model::{
+    entity::terms::{Terms, TermsAcceptance},
+    repository::mongo_repository::MongoRepository,
+};
+use bson::doc;
+
+use super::error::{BadRequest, Error};
+
+// Functions
+pub async fn current_terms(repo: &MongoRepository<Terms>) -> Option<Terms> {
+    repo.find_first(doc! {}, doc! { "version": -1 }).await
+}
+
+/// Proof of consent for the submission, refused unless it accepts the current version.
+/// Nothing is required while no terms are published
+pub async fn accept_terms(
+    repo: &MongoRepository<Terms>,
+    accepted: Option<u32>,
+    mac: &str,
+    ip: Option<IpAddr>,
+) -> Result<Option<TermsAcceptance>, BadRequest> {
+    let Some(terms) = current_terms(repo).await else {
+        return Ok(None);
+    };
+
+    if accepted != Some(terms.version) {
+        return Err(Error::new_bad_request("Terms of use not accepted"));
+    }
+
+    Ok(Some(TermsAcceptance::new(terms.version, mac, ip)))
+}
@@ -1,54 +1,65 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
 use regex::Regex;
 
-use crate::{configurations::config::ClientsConfig, model::entity::client::ClientData};
+use crate::model::entity::{
+    client::ClientData,
+    portal_form::{FieldType, FormField, PortalForm},
+};
+
+const EMAIL_PATTERN: &str = r"^[^@\s]+@[^@\s]+\.[^@\s]+$";
+const PHONE_PATTERN: &str = r"^\+?[0-9 ()-]{8,20}$";
 
 // Structs
 pub struct Validator;
 
 // Impls
 impl Validator {
-    pub fn validate_client( config: &ClientsConfig, data: &ClientData ) -> bool {
-        if let Some(infos) = &config.info {
-            
-            if let Some(name_validated) = &infos.name_validated {
-                let regex_res = Regex::new( name_validated );
-
-                if let Ok(regex) = regex_res {
-                    if !regex.is_match( &data.full_name ) {return false;}
-                }; 
-            }
-            
-            if let Some(email_validated) = &infos.email_validated {
-                let regex_res = Regex::new( email_validated );
-
-                if let Ok(regex) = regex_res {
-                    if !regex.is_match( &data.email ) {return false;}
-                }; 
-            }
-            
-            if let Some(phone_validated) = &infos.phone_validated {
-                let regex_res = Regex::new( phone_validated );
-
-                if let Ok(regex) = regex_res {
-                    if !regex.is_match( &data.phone ) {return false;}
-                }; 
-            }
-            
-            for (field, validate) in infos.fields.iter() {
-                if let Some(value) = data.fields.get(field) {
-                    
-                    if let Ok(regex) = Regex::new( validate ) {
-                        if !regex.is_match(value) { return false; }
-                    }
-
-                }
-                else {
-                    return false;
-                }
-            }
+    /// Checks the data against the form, the errors are keyed by field name
+    pub fn validate_client(form: &PortalForm, data: &ClientData) -> Result<(), HashMap<String, String>> {
+        let errors: HashMap<String, String> = form
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let value = data.value_of(&field.name).filter(|v| !v.trim().is_empty());
+                let error = Self::validate_field(field, value)?;
+
+                Some((field.name.clone(), field.error_message.clone().unwrap_or(error)))
+            })
+            .collect();
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    fn validate_field(field: &FormField, value: Option<&str>) -> Option<String> {
+        let Some(value) = value else {
+            return field.required.then(|| String::from("Field is required"));
+        };
+
+        let valid = match field.kind {
+            FieldType::Text => true,
+            FieldType::Email => Regex::new(EMAIL_PATTERN).unwrap().is_match(value),
+            FieldType::Phone => Regex::new(PHONE_PATTERN).unwrap().is_match(value),
+            FieldType::Select => field
+                .options
+                .as_ref()
+                .is_some_and(|options| options.iter().any(|o| o.value == value)),
+            FieldType::Checkbox => value == "true" || (value == "false" && !field.required),
+            FieldType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+        };
+
+        if !valid {
+            return Some(String::from("Invalid value"));
+        }
 
+        // An invalid pattern in the configuration does not lock the guests out
+        if let Some(Ok(regex)) = field.regex.as_deref().map(Regex::new)
+            && !regex.is_match(value)
+        {
+            return Some(String::from("Invalid value"));
         }
 
-        true
+        None
     }
 }
@@ -0,0 +1,76 @@
+use crate::{
+    configurations::config::{WlanRotation, WlansConfig},
+    model::{
+        entity::psk_rotation::PskRotation,
+        repository::{Repository, mongo_repository::MongoRepository},
+    },
+    unifi::{
+        error::UnifiError,
+        unifi::{UnifiController, Wlan},
+    },
+};
+use bson::doc;
+
+use super::generator::generator_code;
+
+// Functions
+pub async fn latest_rotation(repo: &MongoRepository<PskRotation>, wlan: &WlanRotation) -> Option<PskRotation> {
+    repo.find(doc! { "site": wlan.site.clone(), "ssid": wlan.ssid.clone() })
+        .await
+        .into_iter()
+        .max_by_key(|r| r.rotated_at)
+}
+
+/// Current passphrase of every rotated WLAN
+pub async fn current_passphrases(repo: &MongoRepository<PskRotation>, config: &WlansConfig) -> Vec<PskRotation> {
+    let mut current = vec![];
+    for wlan in config.rotate.iter() {
+        if let Some(rotation) = latest_rotation(repo, wlan).await {
+            current.push(rotation);
+        }
+    }
+
+    current
+}
+
+async fn find_wlan(unifi: &UnifiController, wlan: &WlanRotation) -> Result<Wlan, UnifiError> {
+    unifi
+        .get_wlans(&wlan.site)
+        .await?
+        .into_iter()
+        .find(|w| w.name == wlan.ssid)
+        .ok_or(UnifiError::Api(format!("WLAN {} not found on site {}", wlan.ssid, wlan.site)))
+}
+
+pub async fn rotate_passphrase(
+    unifi: &UnifiController,
+    repo: &MongoRepository<PskRotation>,
+    config: &WlansConfig,
+    wlan: &WlanRotation,
+    by: String,
+) -> Result<PskRotation, UnifiError> {
+    let target = find_wlan(unifi, wlan).await?;
+
+    // WPA2 passphrases take 8 to 63 characters
+    let passphrase = generator_code(config.passphrase_size.clamp(8, 63), config.just_numbers);
+    unifi.set_wlan_passphrase(&wlan.site, &target.id, &passphrase).await?;
+
+    let rotation = PskRotation::new(wlan.site.clone(), wlan.ssid.clone(), passphrase, by);
+    Ok(repo.save(rotation.clone()).await.unwrap_or(rotation))
+}
+
+/// Records the passphrase already set on the controller, `None` when the WLAN has none
+pub async fn import_passphrase(
+    unifi: &UnifiController,
+    repo: &MongoRepository<PskRotation>,
+    wlan: &WlanRotation,
+) -> Result<Option<PskRotation>, UnifiError> {
+    let target = find_wlan(unifi, wlan).await?;
+
+    let Some(passphrase) = target.x_passphrase.filter(|p| !p.is_empty()) else {
+        return Ok(None);
+    };
+
+    let rotation = PskRotation::new(wlan.site.clone(), wlan.ssid.clone(), passphrase, String::from("controller"));
+    Ok(repo.save(rotation.clone()).await.or(Some(rotation)))
+}